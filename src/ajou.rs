@@ -0,0 +1,11 @@
+use ajou_parser::cli::{self, Cli};
+use anyhow::Result;
+use clap::Parser;
+use dotenv::dotenv;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv().ok();
+
+    cli::run(Cli::parse()).await
+}
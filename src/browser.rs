@@ -0,0 +1,191 @@
+use crate::network::NetworkConfig;
+use crate::MY_USER_AGENT;
+use anyhow::{anyhow, Result};
+use chromiumoxide::{
+    fetcher::BrowserFetcherRevisionInfo, handler::viewport::Viewport, Browser, BrowserConfig,
+    BrowserFetcher, BrowserFetcherOptions, Page,
+};
+use std::{fs, path::Path};
+use tokio::time::{sleep, Duration};
+use tokio_stream::StreamExt;
+use tracing::debug;
+
+/// Starts the browser and returns a handle to it.
+///
+/// # Arguments
+///
+/// * `browser_path` - The path to the browser executable (will be downloaded if not found).
+/// * `user_data_dir` - The path to the user data directory (will be created if not found).
+/// * `headless` - Whether to run the browser in headless mode.
+///
+/// # Errors
+///
+/// * If the browser executable cannot be found or downloaded.
+/// * If the user data directory cannot be created.
+/// * If the browser cannot be launched.
+/// * If the browser handler cannot be spawned.
+pub async fn init_browser(
+    browser_path: &Path,
+    user_data_dir: &Path,
+    headless: bool,
+) -> Result<Browser> {
+    fs::create_dir_all(browser_path)?;
+    fs::create_dir_all(user_data_dir)?;
+
+    let browser_info = ensure_browser(browser_path).await?;
+
+    let viewport = Viewport {
+        width: 1440,
+        height: 900,
+        ..Default::default()
+    };
+
+    let mut config = BrowserConfig::builder()
+        .user_data_dir(user_data_dir)
+        .chrome_executable(browser_info.executable_path)
+        .with_head()
+        .no_sandbox()
+        .viewport(viewport)
+        .window_size(1440, 900)
+        .args(vec![format!("--user-agent={}", MY_USER_AGENT)])
+        // Same proxy and resolve overrides as the HTTP client
+        .args(NetworkConfig::from_env()?.browser_args()?);
+
+    if headless {
+        config = config.arg("--headless");
+    }
+
+    let (browser, mut handler) = Browser::launch(config.build().map_err(|e| anyhow!(e))?).await?;
+
+    tokio::spawn(async move {
+        while let Some(h) = handler.next().await {
+            if h.is_err() {
+                debug!("Browser handler error: {:?}", h);
+                break;
+            }
+        }
+    });
+
+    Ok(browser)
+}
+
+pub async fn ensure_browser(path: &Path) -> Result<BrowserFetcherRevisionInfo> {
+    let fetcher = BrowserFetcher::new(BrowserFetcherOptions::builder().with_path(path).build()?);
+
+    Ok(fetcher.fetch().await?)
+}
+
+/// Waits for the page to navigate or for 5 seconds to pass.
+///
+/// # Arguments
+///
+/// * `page` - The page to wait for.
+pub async fn wait_for_page(page: &Page) {
+    tokio::select! {
+        _ = page.wait_for_navigation() => {},
+        _ = sleep(Duration::from_secs(5)) => {},
+    }
+}
+
+pub async fn get_jsession_id(page: &Page) -> Result<Option<String>> {
+    let cookies = page.get_cookies().await?;
+    // println!("{:#?}", cookies);
+    let jsessionid_value = cookies
+        .iter()
+        .find(|cookie| cookie.name == "JSESSIONID")
+        .map(|cookie| cookie.value.clone());
+
+    Ok(jsessionid_value)
+}
+
+pub async fn wait_for_url(page: &Page, target_url: &str, timeout: Duration) -> Result<bool> {
+    let start_time = tokio::time::Instant::now();
+    loop {
+        if page.url().await?.unwrap().eq(target_url) {
+            return Ok(true);
+        }
+        if start_time.elapsed() > timeout {
+            return Ok(false);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+pub async fn login_if_needed(page: &Page) -> Result<()> {
+    if let Ok(user_id_element) = page.find_element("input#userId").await {
+        let id = std::env::var("ID").expect("ID must be set.");
+        let pw = std::env::var("PASSWORD").expect("PASSWORD must be set.");
+
+        user_id_element.click().await?.type_str(&id).await?;
+
+        page.find_element("input#password")
+            .await?
+            .click()
+            .await?
+            .type_str(&pw)
+            .await?;
+
+        page.find_element("a#loginSubmit").await?.click().await?;
+
+        // wait_for_url(
+        //     &page,
+        //     "https://mhaksa.ajou.ac.kr:30443/index.html",
+        //     Duration::from_secs(5),
+        // )
+        // .await?;
+    }
+    Ok(())
+}
+
+/// Logs in to mhaksa through SSO with `ID`/`PASSWORD` in a headless browser and returns the
+/// `JSESSIONID` bound to the HAKSA servlet.
+pub async fn login_and_get_jsession() -> Result<String> {
+    let mut browser = init_browser(Path::new("./browser"), Path::new("./user_data"), true).await?;
+
+    let page = browser
+        .new_page("https://mhaksa.ajou.ac.kr:30443/index.html")
+        .await?;
+
+    if wait_for_url(
+        &page,
+        "https://sso.ajou.ac.kr/jsp/sso/ip/login_form.jsp",
+        Duration::from_secs(5),
+    )
+    .await?
+    {
+        login_if_needed(&page).await?;
+    }
+
+    wait_for_url(
+        &page,
+        "https://mhaksa.ajou.ac.kr:30443/index.html",
+        Duration::from_secs(5),
+    )
+    .await?;
+
+    let mut jsession = match get_jsession_id(&page).await {
+        Ok(Some(jsession_value)) => jsession_value,
+        _ => {
+            browser.close().await?;
+            return Err(anyhow!("JSESSIONID not found"));
+        }
+    };
+
+    while !jsession.contains("chusa_servlet_HAKSA01") {
+        wait_for_url(
+            &page,
+            "https://mhaksa.ajou.ac.kr:30443/index.html",
+            Duration::from_secs(1),
+        )
+        .await?;
+        jsession = get_jsession_id(&page)
+            .await?
+            .ok_or_else(|| anyhow!("JSESSIONID not found"))?;
+    }
+
+    browser.close().await?;
+
+    eprintln!("Browser closed");
+
+    Ok(jsession)
+}
@@ -0,0 +1,221 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// A campus building classrooms are in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Building {
+    /// Canonical id stored on course slots
+    pub id: String,
+    /// Korean name, e.g. "팔달관"
+    pub name: String,
+    pub english_name: String,
+    /// Short code, e.g. "PD"
+    pub code: String,
+    /// Abbreviations and other spellings seen in classrooms, e.g. "팔"
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+/// A room resolved to its building.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Classroom {
+    /// Id of the [`Building`]
+    pub building: String,
+    /// e.g. "309", "B101", `None` if only the building is given
+    pub room: Option<String>,
+}
+
+/// Buildings by id, and the names resolving to them.
+#[derive(Debug)]
+pub struct Campus {
+    buildings: Vec<Building>,
+    by_name: HashMap<String, usize>,
+}
+
+impl Campus {
+    /// Checks that ids, names, codes and aliases are unique.
+    pub fn new(buildings: Vec<Building>) -> Result<Self> {
+        let mut ids = HashMap::new();
+        let mut by_name = HashMap::new();
+
+        for (i, building) in buildings.iter().enumerate() {
+            if ids.insert(building.id.as_str(), i).is_some() {
+                return Err(anyhow!("Duplicate building id {}", building.id));
+            }
+
+            let names = [&building.name, &building.english_name, &building.code]
+                .into_iter()
+                .chain(&building.aliases);
+            for name in names {
+                if let Some(other) = by_name.insert(name_key(name), i) {
+                    if other != i {
+                        return Err(anyhow!(
+                            "{name} is used by both {} and {}",
+                            buildings[other].id,
+                            building.id
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(Campus { buildings, by_name })
+    }
+
+    /// Loaded from the JSON list at `BUILDINGS` on first use, the built-in directory otherwise.
+    pub fn shared() -> Result<&'static Campus> {
+        static SHARED: OnceLock<Campus> = OnceLock::new();
+
+        if let Some(campus) = SHARED.get() {
+            return Ok(campus);
+        }
+        let buildings = match std::env::var("BUILDINGS") {
+            Ok(path) => {
+                let data =
+                    std::fs::read(&path).with_context(|| format!("Reading buildings {path}"))?;
+                serde_json::from_slice(&data)
+                    .with_context(|| format!("Parsing buildings {path}"))?
+            }
+            Err(_) => default_buildings(),
+        };
+        let campus = Campus::new(buildings)?;
+        Ok(SHARED.get_or_init(|| campus))
+    }
+
+    pub fn buildings(&self) -> &[Building] {
+        &self.buildings
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Building> {
+        self.buildings.iter().find(|building| building.id == id)
+    }
+
+    /// The building a name, English name, code or alias stands for (case and spacing ignored).
+    pub fn resolve(&self, name: &str) -> Option<&Building> {
+        self.by_name
+            .get(&name_key(name))
+            .map(|&i| &self.buildings[i])
+    }
+
+    /// Splits a classroom like "팔달관 309", "팔309", "PD-309" or "Paldal Hall 309호" into its
+    /// building and room, the longest known name at the start giving the building.
+    pub fn parse_classroom(&self, classroom: &str) -> Option<Classroom> {
+        let key = name_key(classroom);
+        let (name, &i) = self
+            .by_name
+            .iter()
+            .filter(|(name, _)| key.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())?;
+
+        let room = key[name.len()..]
+            .trim_start_matches(['-', '_'])
+            .trim_end_matches('호')
+            .to_uppercase();
+        // "연구실" after "연" isn't a room of 연암관
+        if !room.is_empty() && !room.chars().any(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        Some(Classroom {
+            building: self.buildings[i].id.clone(),
+            room: Some(room).filter(|room| !room.is_empty()),
+        })
+    }
+}
+
+/// "Paldal Hall", "paldalhall" and "PALDAL HALL" are the same building.
+fn name_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// The buildings courses are held in.
+pub fn default_buildings() -> Vec<Building> {
+    let building =
+        |id: &str, name: &str, english_name: &str, code: &str, aliases: &[&str]| Building {
+            id: id.to_string(),
+            name: name.to_string(),
+            english_name: english_name.to_string(),
+            code: code.to_string(),
+            aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+        };
+
+    vec![
+        building("paldal", "팔달관", "Paldal Hall", "PD", &["팔", "팔달"]),
+        building("yulgok", "율곡관", "Yulgok Hall", "YG", &["율", "율곡"]),
+        building("dasan", "다산관", "Dasan Hall", "DS", &["다", "다산"]),
+        building("wonchun", "원천관", "Wonchun Hall", "WC", &["원", "원천"]),
+        building("seongho", "성호관", "Seongho Hall", "SH", &["성", "성호"]),
+        building("namje", "남제관", "Namje Hall", "NJ", &["남", "남제"]),
+        building("yonam", "연암관", "Yonam Hall", "YA", &["연", "연암"]),
+        building(
+            "general",
+            "종합관",
+            "General Hall",
+            "JH",
+            &["종", "종합", "Jonghap Hall"],
+        ),
+        building(
+            "industry",
+            "산학협력원",
+            "Industry-Academia Collaboration Hall",
+            "SAN",
+            &["산", "산학", "산학원"],
+        ),
+        building("songjae", "송재관", "Songjae Hall", "SJ", &["송", "송재"]),
+        building("ilsin", "일신관", "Ilsin Hall", "IS", &["일신"]),
+        building(
+            "library",
+            "중앙도서관",
+            "Central Library",
+            "LIB",
+            &["도서관"],
+        ),
+        building("gym", "체육관", "Gymnasium", "GYM", &["체"]),
+    ]
+}
+
+#[test]
+fn test_parse_classroom() {
+    let campus = Campus::new(default_buildings()).unwrap();
+    let classroom = |building: &str, room: Option<&str>| {
+        Some(Classroom {
+            building: building.to_string(),
+            room: room.map(String::from),
+        })
+    };
+
+    assert_eq!(
+        campus.parse_classroom("팔달관 309"),
+        classroom("paldal", Some("309"))
+    );
+    assert_eq!(
+        campus.parse_classroom("팔309"),
+        classroom("paldal", Some("309"))
+    );
+    assert_eq!(
+        campus.parse_classroom("Paldal Hall 309호"),
+        classroom("paldal", Some("309"))
+    );
+    assert_eq!(
+        campus.parse_classroom("sh-b101"),
+        classroom("seongho", Some("B101"))
+    );
+    assert_eq!(
+        campus.parse_classroom("산학원 210"),
+        classroom("industry", Some("210"))
+    );
+    assert_eq!(campus.parse_classroom("체육관"), classroom("gym", None));
+    assert_eq!(campus.parse_classroom("연구실"), None);
+    assert_eq!(campus.parse_classroom("온라인"), None);
+
+    assert_eq!(campus.resolve("yulgok hall").unwrap().id, "yulgok");
+
+    let mut duplicate = default_buildings();
+    duplicate[1].aliases.push("PD".to_string());
+    assert!(Campus::new(duplicate).is_err());
+}
@@ -0,0 +1,247 @@
+use crate::deadline::extract_cell_dates;
+use crate::http::HttpClient;
+use anyhow::Result;
+use chrono::NaiveDate;
+use futures::stream::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::options::{FindOptions, UpdateOptions};
+use mongodb::Collection;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+
+/// 학사일정 page, listed per year with `srYear`.
+pub const ACADEMIC_CALENDAR_LINK: &str = "https://www.ajou.ac.kr/kr/bachelor/schedule.do";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventCategory {
+    /// 수강신청, 등록, 휴학/복학 신청
+    Registration,
+    /// 중간/기말고사
+    Exam,
+    /// 개강, 종강
+    Semester,
+    /// 방학, 휴업
+    Break,
+    /// 공휴일, 개교기념일
+    Holiday,
+    Other,
+}
+
+impl EventCategory {
+    /// Guesses the category from the event title.
+    pub fn classify(title: &str) -> Self {
+        let has = |words: &[&str]| words.iter().any(|word| title.contains(word));
+
+        if has(&["방학", "휴업"]) {
+            EventCategory::Break
+        } else if has(&[
+            "공휴일",
+            "휴일",
+            "연휴",
+            "추석",
+            "설날",
+            "개교기념일",
+            "성탄절",
+        ]) {
+            EventCategory::Holiday
+        } else if has(&["시험", "고사"]) {
+            EventCategory::Exam
+        } else if has(&["수강", "등록", "휴학", "복학", "신청"]) {
+            EventCategory::Registration
+        } else if has(&["개강", "종강", "개시", "수업"]) {
+            EventCategory::Semester
+        } else {
+            EventCategory::Other
+        }
+    }
+
+    /// No classes that day.
+    pub fn is_break(self) -> bool {
+        matches!(self, EventCategory::Break | EventCategory::Holiday)
+    }
+}
+
+/// An entry of the academic calendar, `start` to `end` inclusive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AcademicEvent {
+    pub title: String,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub category: EventCategory,
+}
+
+impl AcademicEvent {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        (self.start..=self.end).contains(&date)
+    }
+}
+
+/// Fetches the academic calendar of `year` (`ACADEMIC_CALENDAR` overrides the page URL).
+pub async fn calendar_parse(year: i32) -> Result<Vec<AcademicEvent>> {
+    let link =
+        std::env::var("ACADEMIC_CALENDAR").unwrap_or_else(|_| ACADEMIC_CALENDAR_LINK.to_string());
+    let url = format!("{link}?mode=list&srYear={year}");
+
+    let body = HttpClient::shared()?.get_text(&url).await?.body;
+    Ok(parse_calendar(&body, year))
+}
+
+/// Reads the table rows (or `dt`/`dd` pairs) of the page whose first cell is a date or a range.
+///
+/// Dates without a year are taken in `year`, ranges may run into the next one ("12.22 ~ 01.05").
+pub fn parse_calendar(body: &str, year: i32) -> Vec<AcademicEvent> {
+    let document = Html::parse_document(body);
+    let rows = Selector::parse("tr").unwrap();
+    let cells = Selector::parse("th, td").unwrap();
+    let terms = Selector::parse("dt").unwrap();
+
+    let text = |element: ElementRef| -> String {
+        element
+            .text()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut pairs: Vec<(String, String)> = document
+        .select(&rows)
+        .filter_map(|row| {
+            let mut cells = row.select(&cells).map(text);
+            Some((cells.next()?, cells.collect::<Vec<_>>().join(" ")))
+        })
+        .collect();
+    pairs.extend(document.select(&terms).filter_map(|term| {
+        let description = term
+            .next_siblings()
+            .filter_map(ElementRef::wrap)
+            .find(|sibling| sibling.value().name() == "dd")?;
+        Some((text(term), text(description)))
+    }));
+
+    let Some(new_year) = NaiveDate::from_ymd_opt(year, 1, 1) else {
+        return vec![];
+    };
+
+    let mut events = vec![];
+    for (date, title) in pairs {
+        let title = title.trim().to_string();
+        if title.is_empty() {
+            continue;
+        }
+        // Mostly "MM.DD" or "MM.DD ~ MM.DD", in the calendar's year
+        let Some(dates) = extract_cell_dates(&date, new_year, 0).into_iter().next() else {
+            continue;
+        };
+
+        let event = AcademicEvent {
+            category: EventCategory::classify(&title),
+            title,
+            start: dates.start.unwrap_or(dates.end),
+            end: dates.end,
+        };
+        if !events.contains(&event) {
+            events.push(event);
+        }
+    }
+
+    events
+}
+
+/// Upserts `events` by (title, start), returning how many were new.
+pub async fn save_events(
+    calendar_collection: &Collection<AcademicEvent>,
+    events: &[AcademicEvent],
+) -> Result<u64> {
+    let options = UpdateOptions::builder().upsert(true).build();
+    let mut inserted = 0;
+
+    for event in events {
+        let result = calendar_collection
+            .update_one(
+                doc! { "title": &event.title, "start": event.start.to_string() },
+                doc! { "$set": mongodb::bson::to_document(event)? },
+                options.clone(),
+            )
+            .await?;
+        if result.upserted_id.is_some() {
+            inserted += 1;
+        }
+    }
+
+    Ok(inserted)
+}
+
+/// Stored events overlapping `from..=to`, by start date.
+pub async fn find_events(
+    calendar_collection: &Collection<AcademicEvent>,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<AcademicEvent>> {
+    // Dates are stored as YYYY-MM-DD strings, so they compare in order
+    let filter = doc! {
+        "start": { "$lte": to.to_string() },
+        "end": { "$gte": from.to_string() },
+    };
+    let options = FindOptions::builder().sort(doc! { "start": 1 }).build();
+
+    Ok(calendar_collection
+        .find(filter, options)
+        .await?
+        .try_collect()
+        .await?)
+}
+
+/// The break or holiday `date` falls in, if any.
+pub async fn break_on(
+    calendar_collection: &Collection<AcademicEvent>,
+    date: NaiveDate,
+) -> Result<Option<AcademicEvent>> {
+    Ok(find_events(calendar_collection, date, date)
+        .await?
+        .into_iter()
+        .find(|event| event.category.is_break()))
+}
+
+#[test]
+fn test_parse_calendar() {
+    let body = r#"
+        <table>
+            <tr><th>기간</th><th>내용</th></tr>
+            <tr><td>02.20 (월) ~ 02.24 (금)</td><td>1학기 수강신청</td></tr>
+            <tr><td>03.02 (목)</td><td>1학기 개강</td></tr>
+            <tr><td>04.17 (월) ~ 04.21 (금)</td><td>중간고사</td></tr>
+            <tr><td>06.22 (목) ~ 08.31 (목)</td><td>하계방학</td></tr>
+            <tr><td>04.25</td><td>개교기념일</td></tr>
+            <tr><td>06.26~07.14</td><td>하계 계절수업</td></tr>
+        </table>
+        <dl><dt>12.22 (금) ~ 01.05 (금)</dt><dd>동계 계절수업</dd></dl>
+    "#;
+    let events = parse_calendar(body, 2023);
+    let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+    assert_eq!(events.len(), 7);
+    assert_eq!(
+        events[0],
+        AcademicEvent {
+            title: "1학기 수강신청".to_string(),
+            start: date(2023, 2, 20),
+            end: date(2023, 2, 24),
+            category: EventCategory::Registration,
+        }
+    );
+    assert_eq!(events[1].category, EventCategory::Semester);
+    assert_eq!(events[2].category, EventCategory::Exam);
+    assert!(events[3].category.is_break());
+    assert!(events[3].contains(date(2023, 7, 1)));
+    assert_eq!(
+        (events[4].start, events[4].end),
+        (date(2023, 4, 25), date(2023, 4, 25))
+    );
+    assert_eq!(
+        (events[5].start, events[5].end),
+        (date(2023, 6, 26), date(2023, 7, 14))
+    );
+    assert_eq!(events[6].end, date(2024, 1, 5));
+}
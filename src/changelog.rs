@@ -0,0 +1,484 @@
+use crate::course::{self, Course, CourseCategory, CourseStorage, SaveCounts, Semester};
+use crate::db;
+use crate::digest::MailConfig;
+use crate::http::HttpClient;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use futures::stream::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::options::FindOptions;
+use mongodb::{Client, Collection};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Fields that change with every scrape or semester, or are derived from other fields.
+const IGNORED_FIELDS: &[&str] = &[
+    "row_status",
+    "year",
+    "semester_code",
+    "semester_name",
+    "time_slots",
+    "semester",
+    "categories",
+    "removed_at",
+];
+
+/// Share of a category's stored sections a fetch may drop before it's taken for a truncated
+/// scrape and refused.
+const MAX_REMOVED_SHARE: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A field of a section before and after.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    /// Serialized name, e.g. "classroom"
+    pub field: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+/// What happened to a section between two scrapes or two semesters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CourseChange {
+    /// The semester compared against the older data, e.g. "2023-1"
+    pub semester: String,
+    pub category: Option<CourseCategory>,
+    pub subject_code: String,
+    pub class_number: String,
+    pub subject_name: String,
+    pub kind: ChangeKind,
+    /// Changed fields of a [`ChangeKind::Changed`] section
+    #[serde(default)]
+    pub fields: Vec<FieldChange>,
+    pub detected_at: DateTime<Utc>,
+}
+
+impl CourseChange {
+    /// "~ F001-1 자료구조: classroom 팔달관 309 -> 팔달관 310"
+    pub fn summary(&self) -> String {
+        let sign = match self.kind {
+            ChangeKind::Added => '+',
+            ChangeKind::Removed => '-',
+            ChangeKind::Changed => '~',
+        };
+        let mut summary = format!(
+            "{sign} {}-{} {}",
+            self.subject_code, self.class_number, self.subject_name
+        );
+        for (i, change) in self.fields.iter().enumerate() {
+            let _ = write!(
+                summary,
+                "{} {} {} -> {}",
+                if i == 0 { ":" } else { "," },
+                change.field,
+                value_text(&change.before),
+                value_text(&change.after)
+            );
+        }
+        summary
+    }
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::Null => "(none)".to_string(),
+        Value::String(s) if s.is_empty() => "(none)".to_string(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Compares sections by (subject_code, class_number), in the order of `after` then `before`.
+///
+/// Works for two scrapes of a semester as well as for two semesters, semester fields being
+/// left out of the comparison.
+pub fn diff_courses(
+    semester: &Semester,
+    category: Option<CourseCategory>,
+    before: &[Course],
+    after: &[Course],
+    now: DateTime<Utc>,
+) -> Result<Vec<CourseChange>> {
+    let key = |c: &Course| (c.subject_code.clone(), c.class_number.clone());
+    let before_by_key: HashMap<_, _> = before.iter().map(|c| (key(c), c)).collect();
+    let after_by_key: HashMap<_, _> = after.iter().map(|c| (key(c), c)).collect();
+
+    let change = |course: &Course, kind, fields| CourseChange {
+        semester: semester.to_string(),
+        category,
+        subject_code: course.subject_code.clone(),
+        class_number: course.class_number.clone(),
+        subject_name: course.subject_korean_name.clone(),
+        kind,
+        fields,
+        detected_at: now,
+    };
+
+    let mut changes = vec![];
+    for course in after {
+        match before_by_key.get(&key(course)) {
+            None => changes.push(change(course, ChangeKind::Added, vec![])),
+            Some(old) => {
+                let fields = diff_fields(old, course)?;
+                if !fields.is_empty() {
+                    changes.push(change(course, ChangeKind::Changed, fields));
+                }
+            }
+        }
+    }
+    for course in before {
+        if !after_by_key.contains_key(&key(course)) {
+            changes.push(change(course, ChangeKind::Removed, vec![]));
+        }
+    }
+
+    // A section listed twice in a scrape shouldn't be reported twice
+    let mut seen = HashSet::new();
+    changes.retain(|c| seen.insert((c.subject_code.clone(), c.class_number.clone(), c.kind)));
+    Ok(changes)
+}
+
+fn diff_fields(before: &Course, after: &Course) -> Result<Vec<FieldChange>> {
+    let object = |course: &Course| -> Result<Map<String, Value>> {
+        match serde_json::to_value(course)? {
+            Value::Object(map) => Ok(map),
+            _ => Err(anyhow!("Course isn't serialized as an object")),
+        }
+    };
+    let (before, mut after) = (object(before)?, object(after)?);
+
+    Ok(before
+        .into_iter()
+        .filter(|(field, _)| !IGNORED_FIELDS.contains(&field.as_str()))
+        .filter_map(|(field, before)| {
+            let after = after.remove(&field).unwrap_or(Value::Null);
+            (before != after).then_some(FieldChange {
+                field,
+                before,
+                after,
+            })
+        })
+        .collect())
+}
+
+/// Sections of `stored` that `changes` report as removed.
+pub fn removed_sections<'a>(stored: &'a [Course], changes: &[CourseChange]) -> Vec<&'a Course> {
+    let removed: HashSet<(&str, &str)> = changes
+        .iter()
+        .filter(|change| change.kind == ChangeKind::Removed)
+        .map(|change| (change.subject_code.as_str(), change.class_number.as_str()))
+        .collect();
+    stored
+        .iter()
+        .filter(|course| {
+            removed.contains(&(course.subject_code.as_str(), course.class_number.as_str()))
+        })
+        .collect()
+}
+
+/// Whether dropping `removed` of `stored` sections looks like a truncated scrape rather than
+/// sections actually closing.
+fn too_many_removed(stored: usize, removed: usize) -> bool {
+    removed > 0 && removed as f64 > stored as f64 * MAX_REMOVED_SHARE
+}
+
+/// What [`store_fetch`] did to a category.
+#[derive(Debug)]
+pub struct StoredFetch {
+    pub counts: SaveCounts,
+    /// Sections no longer listed, marked as removed
+    pub removed: u64,
+    pub changes: Vec<CourseChange>,
+}
+
+/// Stores a fresh scrape of a category: diffs it against what's stored, saves it, marks the
+/// sections it no longer lists as removed, then records the changes.
+///
+/// Removed sections are reported once, and nothing is recorded if storing fails. A scrape
+/// dropping more than [`MAX_REMOVED_SHARE`] of the stored sections is refused unless `force`.
+pub async fn store_fetch(
+    client: &Client,
+    storage: CourseStorage,
+    semester: &Semester,
+    category: CourseCategory,
+    fetched: &[Course],
+    force: bool,
+) -> Result<StoredFetch> {
+    let now = Utc::now();
+    let stored = course::stored_courses(client, storage, semester, category).await?;
+    let changes = diff_courses(semester, Some(category), &stored, fetched, now)?;
+    let removed = removed_sections(&stored, &changes);
+    if !force && too_many_removed(stored.len(), removed.len()) {
+        return Err(anyhow!(
+            "{} of the {} stored sections are missing from the scrape, not storing it \
+             (--force to store anyway)",
+            removed.len(),
+            stored.len()
+        ));
+    }
+
+    let counts = course::save_courses(client, storage, semester, category, fetched).await?;
+    let removed =
+        course::remove_courses(client, storage, semester, category, &removed, now).await?;
+    save_changes(&db::course_changelog_collection(client), &changes).await?;
+
+    Ok(StoredFetch {
+        counts,
+        removed,
+        changes,
+    })
+}
+
+/// Appends `changes` to the changelog.
+pub async fn save_changes(
+    changelog_collection: &Collection<CourseChange>,
+    changes: &[CourseChange],
+) -> Result<()> {
+    if !changes.is_empty() {
+        changelog_collection.insert_many(changes, None).await?;
+    }
+    Ok(())
+}
+
+/// Changes of `semester` detected since `since`, oldest first.
+pub async fn find_changes(
+    changelog_collection: &Collection<CourseChange>,
+    semester: &Semester,
+    since: DateTime<Utc>,
+) -> Result<Vec<CourseChange>> {
+    let filter = doc! {
+        "semester": semester.to_string(),
+        // Stored as RFC 3339 strings, so they compare in order
+        "detected_at": { "$gte": since.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true) },
+    };
+    let options = FindOptions::builder()
+        .sort(doc! { "detected_at": 1 })
+        .build();
+
+    Ok(changelog_collection
+        .find(filter, options)
+        .await?
+        .try_collect()
+        .await?)
+}
+
+/// Somewhere course changes are pushed to once a fetch is done.
+pub trait ChangeSink: Send + Sync {
+    fn send<'a>(&'a self, changes: &'a [CourseChange]) -> BoxFuture<'a, Result<()>>;
+}
+
+/// POSTs the changes as a JSON array.
+pub struct WebhookSink {
+    pub url: String,
+}
+
+impl ChangeSink for WebhookSink {
+    fn send<'a>(&'a self, changes: &'a [CourseChange]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            HttpClient::shared()?
+                .post_json(
+                    &self.url,
+                    headers,
+                    &serde_json::to_value(changes)?,
+                    Duration::from_secs(10),
+                    |_| false,
+                )
+                .await?;
+            Ok(())
+        })
+    }
+}
+
+/// Mails the summaries to the digest recipients.
+impl ChangeSink for MailConfig {
+    fn send<'a>(&'a self, changes: &'a [CourseChange]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let summaries: Vec<String> = changes.iter().map(CourseChange::summary).collect();
+            let html = summaries
+                .iter()
+                .map(|summary| format!("<li>{}</li>", html_escape(summary)))
+                .collect::<String>();
+            self.send_mail(
+                &format!("[아주대] 강의 변경 {}건", changes.len()),
+                summaries.join("\n"),
+                format!("<ul>{html}</ul>"),
+            )
+            .await
+        })
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// `COURSE_CHANGES_WEBHOOK` (a URL) and, with `COURSE_CHANGES_MAIL=true`, the digest mail setup.
+pub fn sinks_from_env() -> Result<Vec<Box<dyn ChangeSink>>> {
+    let mut sinks: Vec<Box<dyn ChangeSink>> = vec![];
+
+    if let Ok(url) = std::env::var("COURSE_CHANGES_WEBHOOK") {
+        if !url.trim().is_empty() {
+            sinks.push(Box::new(WebhookSink {
+                url: url.trim().to_string(),
+            }));
+        }
+    }
+    if std::env::var("COURSE_CHANGES_MAIL").is_ok_and(|mail| mail.trim() == "true") {
+        let mail = MailConfig::from_env()?
+            .ok_or_else(|| anyhow!("COURSE_CHANGES_MAIL needs SMTP_HOST"))?;
+        sinks.push(Box::new(mail));
+    }
+
+    Ok(sinks)
+}
+
+/// Sends `changes` to every sink, reporting failures without stopping at the first one.
+pub async fn notify(sinks: &[Box<dyn ChangeSink>], changes: &[CourseChange]) -> Result<()> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for sink in sinks {
+        if let Err(e) = sink.send(changes).await {
+            eprintln!("Error sending course changes: {e:#}");
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(anyhow!("{failed} of {} sinks failed", sinks.len()));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_diff_courses() {
+    let course = |code: &str, class: &str, classroom: &str| Course {
+        subject_code: code.to_string(),
+        class_number: class.to_string(),
+        subject_korean_name: code.to_string(),
+        classroom: classroom.to_string(),
+        main_lecturer_name: "홍길동".to_string(),
+        ..Default::default()
+    };
+    let before = vec![
+        course("F001", "1", "팔달관 309"),
+        course("F001", "2", "팔달관 310"),
+        course("F002", "1", "율곡관 101"),
+    ];
+    let mut moved = course("F001", "1", "팔달관 409");
+    moved.main_lecturer_name = "김아주".to_string();
+    moved.row_status = 1;
+    let after = vec![
+        moved,
+        course("F001", "2", "팔달관 310"),
+        course("F003", "1", "다산관 201"),
+    ];
+
+    let semester: Semester = "2023-1".parse().unwrap();
+    let changes = diff_courses(
+        &semester,
+        Some(CourseCategory::Major),
+        &before,
+        &after,
+        Utc::now(),
+    )
+    .unwrap();
+
+    assert_eq!(changes.len(), 3);
+    assert_eq!(changes[0].kind, ChangeKind::Changed);
+    assert_eq!(
+        changes[0].fields,
+        vec![
+            FieldChange {
+                field: "main_lecturer_name".to_string(),
+                before: Value::from("홍길동"),
+                after: Value::from("김아주"),
+            },
+            FieldChange {
+                field: "classroom".to_string(),
+                before: Value::from("팔달관 309"),
+                after: Value::from("팔달관 409"),
+            },
+        ]
+    );
+    assert_eq!(
+        changes[0].summary(),
+        "~ F001-1 F001: main_lecturer_name 홍길동 -> 김아주, classroom 팔달관 309 -> 팔달관 409"
+    );
+    assert_eq!(
+        (changes[1].kind, changes[1].subject_code.as_str()),
+        (ChangeKind::Added, "F003")
+    );
+    assert_eq!(
+        (changes[2].kind, changes[2].subject_code.as_str()),
+        (ChangeKind::Removed, "F002")
+    );
+}
+
+#[test]
+fn test_fetch_twice() {
+    let course = |code: &str| Course {
+        subject_code: code.to_string(),
+        class_number: "1".to_string(),
+        ..Default::default()
+    };
+    let semester: Semester = "2023-1".parse().unwrap();
+    let diff = |stored: &[Course], fetched: &[Course]| {
+        diff_courses(&semester, None, stored, fetched, Utc::now()).unwrap()
+    };
+
+    // What storing a fetch leaves: the fetched sections, upserted, without the removed ones
+    let store = |stored: &[Course], fetched: &[Course], changes: &[CourseChange]| {
+        let removed = removed_sections(stored, changes);
+        let mut after: Vec<Course> = stored
+            .iter()
+            .filter(|course| !removed.iter().any(|r| std::ptr::eq(*r, *course)))
+            .filter(|course| {
+                !fetched.iter().any(|f| {
+                    (&f.subject_code, &f.class_number)
+                        == (&course.subject_code, &course.class_number)
+                })
+            })
+            .cloned()
+            .collect();
+        after.extend(fetched.iter().cloned());
+        after
+    };
+
+    let stored = vec![course("F001"), course("F002")];
+    let fetched = vec![course("F001"), course("F003")];
+
+    let changes = diff(&stored, &fetched);
+    assert_eq!(changes.len(), 2);
+    assert_eq!(removed_sections(&stored, &changes).len(), 1);
+    let stored = store(&stored, &fetched, &changes);
+
+    assert_eq!(diff(&stored, &fetched), vec![]);
+}
+
+#[test]
+fn test_too_many_removed() {
+    assert!(!too_many_removed(0, 0));
+    assert!(!too_many_removed(10, 0));
+    assert!(!too_many_removed(10, 5));
+    assert!(too_many_removed(10, 6));
+    // An empty scrape of a stored category
+    assert!(too_many_removed(3, 3));
+}
@@ -0,0 +1,1447 @@
+use crate::building::Campus;
+use crate::calendar::{self, AcademicEvent};
+use crate::changelog::{self, CourseChange};
+use crate::course::{
+    self, CategoryCourses, Course, CourseCategory, CourseStorage, Semester,
+    DEFAULT_FETCH_CONCURRENCY,
+};
+use crate::deadline::Deadline;
+use crate::department::Directory;
+use crate::digest::{self, DigestPeriod, MailConfig, Markup};
+use crate::export::{self, ExportFormat, ExportWriter, NoticeQuery};
+use crate::meal::{self, Meal, Restaurant};
+use crate::notice::{self, DEFAULT_NUM_ARTICLES};
+use crate::occupancy::OccupancyIndex;
+use crate::people::{FieldPolicy, PeopleClient, Person, Redaction, SearchBy};
+use crate::session::{self, HaksaSession};
+use crate::timetable::{self, Constraints, Pick, Timetable};
+use crate::{db, ics, scheduler, Notice, AJOU_LINK};
+use anyhow::{anyhow, Context, Result};
+use chrono::{Datelike, NaiveDate, NaiveTime, TimeZone, Timelike, Utc, Weekday};
+use chrono_tz::Asia::Seoul;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// `ajou`: notices and courses of Ajou University from the command line.
+///
+/// `updater` is an alias for `ajou serve` and `courser` for `ajou courses fetch`.
+#[derive(Debug, Parser)]
+#[command(name = "ajou", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// Output format
+    #[arg(long, global = true, value_enum, default_value_t = Format::Table)]
+    pub format: Format,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Notices of the Ajou notice board
+    #[command(subcommand)]
+    Notices(NoticesCommand),
+    /// Courses of mhaksa (requires `ID`, `PASSWORD` and `COURSE`)
+    #[command(subcommand)]
+    Courses(CoursesCommand),
+    /// The academic calendar (학사일정)
+    #[command(subcommand)]
+    Calendar(CalendarCommand),
+    /// Menus of the dormitory and staff restaurants
+    #[command(subcommand)]
+    Meals(MealsCommand),
+    /// Classroom occupancy from the stored course timetables
+    Rooms {
+        #[command(subcommand)]
+        command: RoomsCommand,
+        /// e.g. 2023-1; `COURSE_SEMESTER` or the current one by default
+        #[arg(long, global = true)]
+        semester: Option<Semester>,
+    },
+    /// Searches the staff and faculty directory
+    People {
+        query: String,
+        #[arg(long, value_enum, default_value_t = SearchBy::Name)]
+        by: SearchBy,
+        /// Phone policy
+        #[arg(long, value_enum, default_value_t = FieldPolicy::Show)]
+        phone: FieldPolicy,
+        /// Email policy
+        #[arg(long, value_enum, default_value_t = FieldPolicy::Show)]
+        email: FieldPolicy,
+        /// Office policy
+        #[arg(long, value_enum, default_value_t = FieldPolicy::Show)]
+        office: FieldPolicy,
+    },
+    /// Lists the department directory used to resolve notice writers
+    Departments,
+    /// Lists the building directory used to resolve classrooms
+    Buildings,
+    /// Keeps the `notice` collection up to date (the `updater` loop)
+    Serve,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Table,
+    Json,
+    Ndjson,
+    /// UTF-8 with BOM
+    Csv,
+    Parquet,
+}
+
+impl Format {
+    /// The streaming format, `None` for the ones printed all at once.
+    fn export_format(self) -> Option<ExportFormat> {
+        match self {
+            Format::Table | Format::Json => None,
+            Format::Ndjson => Some(ExportFormat::Ndjson),
+            Format::Csv => Some(ExportFormat::Csv),
+            Format::Parquet => Some(ExportFormat::Parquet),
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Write to this file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Comma separated fields to export, in order (all by default)
+    #[arg(long, value_delimiter = ',')]
+    pub fields: Vec<String>,
+    /// Export a fresh scrape instead of the stored documents
+    #[arg(long)]
+    pub scrape: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct NoticeFilter {
+    /// Board list URL
+    #[arg(long, default_value = AJOU_LINK)]
+    pub board: String,
+    /// Only notices of this category (e.g. 학사, 장학)
+    #[arg(long)]
+    pub category: Option<String>,
+    /// Only notices of this department id and the ones below it (see `ajou departments`)
+    #[arg(long)]
+    pub department: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum NoticesCommand {
+    /// Lists the latest notices
+    List {
+        #[arg(short, long, default_value_t = DEFAULT_NUM_ARTICLES)]
+        limit: usize,
+        #[command(flatten)]
+        filter: NoticeFilter,
+    },
+    /// Searches the board by keyword
+    Search {
+        keyword: String,
+        #[arg(short, long, default_value_t = DEFAULT_NUM_ARTICLES)]
+        limit: usize,
+        #[command(flatten)]
+        filter: NoticeFilter,
+    },
+    /// Inserts notices posted since the last stored one
+    Sync {
+        /// Print what would be written without touching MongoDB
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Inserts any of the latest `count` notices that are missing
+    Backfill {
+        #[arg(short, long, default_value_t = 100)]
+        count: usize,
+        /// Print what would be written without touching MongoDB
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Summarizes the stored notices of a day or a week, grouped by category and writer
+    Digest {
+        #[arg(long, value_enum, default_value_t = DigestPeriod::Daily)]
+        period: DigestPeriod,
+        /// Last day of the window (YYYY-MM-DD), the last complete one by default
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        #[arg(long, value_enum, default_value_t = Markup::Text)]
+        markup: Markup,
+        /// Email it with the SMTP_*/DIGEST_* settings instead of printing it
+        #[arg(long)]
+        send: bool,
+    },
+    /// Lists the deadlines of stored notices ending in the next days
+    Deadlines {
+        #[arg(short, long, default_value_t = 14)]
+        days: i64,
+    },
+    /// iCalendar feed of the deadlines of stored notices
+    Ics {
+        /// Only notices of this category (e.g. 학사, 장학)
+        #[arg(long)]
+        category: Option<String>,
+        /// Only notices of this board list URL
+        #[arg(long)]
+        board: Option<String>,
+        /// Write to this file instead of stdout
+        #[arg(short, long, conflicts_with = "serve")]
+        output: Option<PathBuf>,
+        /// Serve it at http://<addr>/notices.ics (e.g. 127.0.0.1:8080) instead
+        #[arg(long)]
+        serve: Option<SocketAddr>,
+    },
+    /// Resolves the writer of every stored notice again, e.g. after editing `DEPARTMENTS`
+    Departments {
+        /// Print how many notices would change without touching MongoDB
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Dumps the stored notices (use --format csv|ndjson|parquet)
+    Export {
+        #[arg(short, long, value_parser = clap::value_parser!(i64).range(1..))]
+        limit: Option<i64>,
+        /// Only notices of this category (e.g. 학사, 장학)
+        #[arg(long)]
+        category: Option<String>,
+        /// Posted on or after (YYYY-MM-DD)
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Posted on or before (YYYY-MM-DD)
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        /// Only notices of this department id and the ones below it
+        #[arg(long)]
+        department: Option<String>,
+        #[command(flatten)]
+        export: ExportArgs,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CalendarCommand {
+    /// Scrapes the academic calendar of a year into the `calendar` collection
+    Fetch {
+        /// This year by default
+        #[arg(long)]
+        year: Option<i32>,
+        /// Print what would be written without touching MongoDB
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Lists the stored events of a year
+    List {
+        #[arg(long)]
+        year: Option<i32>,
+    },
+    /// iCalendar feed of the stored events of a year
+    Ics {
+        #[arg(long)]
+        year: Option<i32>,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Tells whether a day (today by default) is a break or a holiday
+    Break { date: Option<NaiveDate> },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MealsCommand {
+    /// Scrapes the menus into the `meal` collection
+    Fetch {
+        /// Every restaurant if omitted
+        #[arg(long, value_enum)]
+        restaurant: Option<Restaurant>,
+        /// Today (KST) by default
+        #[arg(long)]
+        date: Option<NaiveDate>,
+        /// The whole week of `date`, Monday to Sunday
+        #[arg(long)]
+        week: bool,
+        /// Print what would be written without touching MongoDB
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Today's menu, scraped if it isn't stored yet
+    Today {
+        #[arg(long, value_enum)]
+        restaurant: Option<Restaurant>,
+    },
+}
+
+/// A time of the week, now (KST) by default.
+#[derive(Debug, Args)]
+pub struct WeekTime {
+    /// e.g. mon, tue
+    #[arg(long)]
+    pub weekday: Option<Weekday>,
+    /// e.g. 10:30
+    #[arg(long)]
+    pub time: Option<NaiveTime>,
+}
+
+impl WeekTime {
+    fn resolve(&self) -> (Weekday, NaiveTime) {
+        let now = Utc::now().with_timezone(&Seoul);
+        let time = now.time().with_nanosecond(0).unwrap_or(now.time());
+        (
+            self.weekday.unwrap_or(now.weekday()),
+            self.time.unwrap_or(time),
+        )
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RoomsCommand {
+    /// Rooms of a building no course is in
+    Free {
+        /// Id, name, code or alias, e.g. 팔달관, PD
+        building: String,
+        #[command(flatten)]
+        at: WeekTime,
+    },
+    /// The class going on in a room or the next one
+    Next {
+        building: String,
+        room: String,
+        #[command(flatten)]
+        at: WeekTime,
+    },
+    /// Weekly timetable of a room
+    Week { building: String, room: String },
+}
+
+#[derive(Debug, Args)]
+pub struct CourseSelection {
+    /// Category code or name (e.g. U0209001, 전공과목, major), all if omitted
+    #[arg(long = "category")]
+    pub categories: Vec<CourseCategory>,
+    /// e.g. 2023-1, 2023-summer, 2023-2, 2023-winter; `COURSE_SEMESTER` or the current one by default
+    #[arg(long)]
+    pub semester: Option<Semester>,
+}
+
+impl CourseSelection {
+    fn categories(&self) -> Vec<CourseCategory> {
+        if self.categories.is_empty() {
+            CourseCategory::ALL.to_vec()
+        } else {
+            self.categories.clone()
+        }
+    }
+
+    fn semester(&self) -> Result<Semester> {
+        match self.semester {
+            Some(semester) => Ok(semester),
+            None => Semester::configured(),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CoursesCommand {
+    /// Logs in through the browser and stores the courses of each category
+    Fetch {
+        #[command(flatten)]
+        selection: CourseSelection,
+        /// Categories fetched at once
+        #[arg(long, default_value_t = DEFAULT_FETCH_CONCURRENCY)]
+        concurrency: usize,
+        /// Print what would be written without touching MongoDB
+        #[arg(long)]
+        dry_run: bool,
+        /// Store a category even if the scrape drops most of its stored sections
+        #[arg(long)]
+        force: bool,
+    },
+    /// Searches stored courses by subject name, code or lecturer
+    Search {
+        /// Every course (of `--building`) if omitted
+        keyword: Option<String>,
+        /// Only courses meeting in this building (id, name, code or alias, e.g. 팔달관, PD)
+        #[arg(long)]
+        building: Option<String>,
+        #[command(flatten)]
+        selection: CourseSelection,
+    },
+    /// Tells which of the picked sections meet at the same time
+    Check {
+        /// Sections as SUBJECT_CODE-CLASS_NUMBER, e.g. F001-1
+        #[arg(required = true, value_parser = parse_pick)]
+        picks: Vec<Pick>,
+        #[command(flatten)]
+        selection: CourseSelection,
+    },
+    /// Builds conflict-free timetables out of a wishlist of subject codes, best first
+    Plan {
+        /// Subject codes, earlier ones kept first when not all of them fit
+        #[arg(required = true)]
+        subjects: Vec<String>,
+        /// No class starting before, e.g. 10:00
+        #[arg(long)]
+        earliest: Option<NaiveTime>,
+        /// No class ending after, e.g. 18:00
+        #[arg(long)]
+        latest: Option<NaiveTime>,
+        /// Days without classes, e.g. fri (repeatable)
+        #[arg(long = "free-day")]
+        free_days: Vec<Weekday>,
+        #[arg(long)]
+        max_credits: Option<f32>,
+        /// Lecturers ranked higher (repeatable)
+        #[arg(long = "lecturer")]
+        lecturers: Vec<String>,
+        #[arg(short, long, default_value_t = 5)]
+        limit: usize,
+        #[command(flatten)]
+        selection: CourseSelection,
+    },
+    /// Compares a fresh scrape against the stored courses, or two stored semesters
+    Diff {
+        /// Compare the stored courses of this older semester against `--semester` instead
+        #[arg(long)]
+        against: Option<Semester>,
+        #[command(flatten)]
+        selection: CourseSelection,
+    },
+    /// Lists the changes recorded by `fetch` in the `course_changelog` collection
+    Changes {
+        /// A week ago by default (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<NaiveDate>,
+        #[command(flatten)]
+        selection: CourseSelection,
+    },
+    /// Dumps the stored courses (use --format csv|ndjson|parquet)
+    Export {
+        #[command(flatten)]
+        selection: CourseSelection,
+        #[command(flatten)]
+        export: ExportArgs,
+    },
+    /// Deletes all but the latest copy of sections stored more than once, then indexes them
+    Dedupe {
+        #[command(flatten)]
+        selection: CourseSelection,
+    },
+    /// Copies the `course_<semester>_<category>` collections into the unified `courses` one
+    Migrate {
+        /// Only this semester, every stored one by default
+        #[arg(long)]
+        semester: Option<Semester>,
+    },
+    /// Deletes the stored mhaksa session, the next fetch logging in through the browser again
+    Logout,
+}
+
+pub async fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Command::Notices(command) => run_notices(command, cli.format).await,
+        Command::Courses(command) => run_courses(command, cli.format).await,
+        Command::Calendar(command) => run_calendar(command, cli.format).await,
+        Command::Meals(command) => run_meals(command, cli.format).await,
+        Command::Rooms { command, semester } => run_rooms(command, semester, cli.format).await,
+        Command::People {
+            query,
+            by,
+            phone,
+            email,
+            office,
+        } => {
+            let redaction = Redaction {
+                phone,
+                email,
+                office,
+            };
+            let people = PeopleClient::default()
+                .search_redacted(by, &query, &redaction)
+                .await?;
+            print_people(&people, cli.format)
+        }
+        Command::Departments => print_departments(cli.format),
+        Command::Buildings => print_buildings(cli.format),
+        Command::Serve => {
+            println!("Connecting to mongo-db...");
+            let client = db::connect().await?;
+            println!("Connected!");
+
+            scheduler::run_updater(
+                &db::notice_collection(&client),
+                &db::calendar_collection(&client),
+            )
+            .await;
+            Ok(())
+        }
+    }
+}
+
+async fn run_notices(command: NoticesCommand, format: Format) -> Result<()> {
+    match command {
+        NoticesCommand::List { limit, filter } => {
+            let notices = notice::notice_parse_board(&filter.board, "ajou", Some(limit)).await?;
+            print_notices(&filter_notices(notices, &filter)?, format)
+        }
+        NoticesCommand::Search {
+            keyword,
+            limit,
+            filter,
+        } => {
+            let notices = notice::notice_parse_board(&filter.board, &keyword, Some(limit)).await?;
+            print_notices(&filter_notices(notices, &filter)?, format)
+        }
+        NoticesCommand::Sync { dry_run } => {
+            let client = db::connect().await?;
+            let notices = notice::sync_notices(&db::notice_collection(&client), dry_run).await?;
+            print_notices(&notices, format)
+        }
+        NoticesCommand::Backfill { count, dry_run } => {
+            let client = db::connect().await?;
+            let notices =
+                notice::backfill_notices(&db::notice_collection(&client), count, dry_run).await?;
+            print_notices(&notices, format)
+        }
+        NoticesCommand::Digest {
+            period,
+            to,
+            markup,
+            send,
+        } => {
+            let (from, to) = match to {
+                Some(to) => period.window(to),
+                None => period.last_window(Utc::now().with_timezone(&Seoul)),
+            };
+
+            let client = db::connect().await?;
+            let digest =
+                digest::collect_digest(&db::notice_collection(&client), period, from, to).await?;
+
+            if send {
+                let mail = MailConfig::from_env()?
+                    .ok_or_else(|| anyhow!("Set SMTP_HOST, DIGEST_FROM and DIGEST_TO to send"))?;
+                mail.send(&digest).await?;
+                println!(
+                    "Sent {} notices to {} recipients",
+                    digest.len(),
+                    mail.to.len()
+                );
+            } else if format == Format::Json {
+                println!("{}", serde_json::to_string_pretty(&digest)?);
+            } else {
+                print!("{}", digest.render(markup));
+            }
+            Ok(())
+        }
+        NoticesCommand::Deadlines { days } => {
+            let today = Utc::now().with_timezone(&Seoul).date_naive();
+            let until = today + chrono::Duration::days(days);
+
+            let client = db::connect().await?;
+            // Dates are stored as YYYY-MM-DD strings, so they compare in order
+            let filter = doc! {
+                "deadlines.end": { "$gte": today.to_string(), "$lte": until.to_string() }
+            };
+            let notices: Vec<Notice> = db::notice_collection(&client)
+                .find(filter, None)
+                .await?
+                .try_collect()
+                .await?;
+
+            let mut upcoming: Vec<(&Deadline, &Notice)> = notices
+                .iter()
+                .flat_map(|notice| notice.deadlines.iter().map(move |d| (d, notice)))
+                .filter(|(deadline, _)| (today..=until).contains(&deadline.end))
+                .collect();
+            upcoming.sort_by_key(|(deadline, _)| deadline.due_at());
+
+            if format != Format::Table {
+                let notices: Vec<&Notice> = upcoming.iter().map(|(_, notice)| *notice).collect();
+                return print_items(&notices, format);
+            }
+            for (deadline, notice) in upcoming {
+                println!(
+                    "{}\t{}\t[{}] {}\t{}",
+                    deadline.due_at().format("%Y-%m-%d %H:%M"),
+                    deadline.text,
+                    notice.category,
+                    notice.title,
+                    notice.link
+                );
+            }
+            Ok(())
+        }
+        NoticesCommand::Ics {
+            category,
+            board,
+            output,
+            serve,
+        } => {
+            let client = db::connect().await?;
+            let collection = db::notice_collection(&client);
+            let query = NoticeQuery {
+                category,
+                board,
+                ..Default::default()
+            };
+
+            if let Some(addr) = serve {
+                // The server takes its filters from the request
+                return ics::serve_ics(collection, addr).await;
+            }
+            match output {
+                Some(path) => ics::write_ics(&collection, &query, &path).await,
+                None => {
+                    print!("{}", ics::notices_ics(&collection, &query).await?);
+                    Ok(())
+                }
+            }
+        }
+        NoticesCommand::Departments { dry_run } => {
+            let client = db::connect().await?;
+            let changed =
+                notice::resolve_departments(&db::notice_collection(&client), dry_run).await?;
+            if dry_run {
+                println!("{changed} notices would change department");
+            } else {
+                println!("Updated the department of {changed} notices");
+            }
+            Ok(())
+        }
+        NoticesCommand::Export {
+            limit,
+            category,
+            from,
+            to,
+            department,
+            export,
+        } => {
+            let query = NoticeQuery {
+                category,
+                from,
+                to,
+                departments: department_subtree(&department)?,
+                board: None,
+                limit,
+            };
+
+            if export.scrape {
+                let nums = limit.map_or(DEFAULT_NUM_ARTICLES, |limit| limit as usize);
+                let notices: Vec<Notice> = notice::notice_parse("ajou", Some(nums))
+                    .await?
+                    .into_iter()
+                    .rev()
+                    .filter(|notice| query.matches(notice))
+                    .collect();
+                return export_items(&notices, &export, format);
+            }
+
+            let client = db::connect().await?;
+            let collection = db::notice_collection(&client);
+            match export_writer(&export, format)? {
+                Some(writer) => {
+                    let count = export::export_notices(&collection, &query, writer).await?;
+                    eprintln!("Exported {count} notices");
+                    Ok(())
+                }
+                None => {
+                    let notices: Vec<Notice> = query
+                        .find(&collection, query.filter())
+                        .await?
+                        .try_collect()
+                        .await?;
+                    print_notices(&notices, format)
+                }
+            }
+        }
+    }
+}
+
+async fn run_calendar(command: CalendarCommand, format: Format) -> Result<()> {
+    let today = Utc::now().with_timezone(&Seoul).date_naive();
+    let year_range = |year: Option<i32>| {
+        let year = year.unwrap_or(today.year());
+        (
+            NaiveDate::from_ymd_opt(year, 1, 1).unwrap_or(today),
+            NaiveDate::from_ymd_opt(year, 12, 31).unwrap_or(today),
+        )
+    };
+
+    match command {
+        CalendarCommand::Fetch { year, dry_run } => {
+            let events = calendar::calendar_parse(year.unwrap_or(today.year())).await?;
+            if !dry_run {
+                let client = db::connect().await?;
+                let inserted =
+                    calendar::save_events(&db::calendar_collection(&client), &events).await?;
+                eprintln!("{} events, {inserted} new", events.len());
+            }
+            print_events(&events, format)
+        }
+        CalendarCommand::List { year } => {
+            let (from, to) = year_range(year);
+            let client = db::connect().await?;
+            let events = calendar::find_events(&db::calendar_collection(&client), from, to).await?;
+            print_events(&events, format)
+        }
+        CalendarCommand::Ics { year, output } => {
+            let (from, to) = year_range(year);
+            let client = db::connect().await?;
+            let ics = ics::academic_ics(&db::calendar_collection(&client), from, to).await?;
+            match output {
+                Some(path) => ics::write_atomic(&path, ics).await,
+                None => {
+                    print!("{ics}");
+                    Ok(())
+                }
+            }
+        }
+        CalendarCommand::Break { date } => {
+            let date = date.unwrap_or(today);
+            let client = db::connect().await?;
+            match calendar::break_on(&db::calendar_collection(&client), date).await? {
+                Some(event) => {
+                    println!("{date}: {} ({} ~ {})", event.title, event.start, event.end)
+                }
+                None => println!("{date}: not a break"),
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn run_meals(command: MealsCommand, format: Format) -> Result<()> {
+    let today = Utc::now().with_timezone(&Seoul).date_naive();
+    let restaurants = |restaurant: Option<Restaurant>| match restaurant {
+        Some(restaurant) => vec![restaurant],
+        None => Restaurant::ALL.to_vec(),
+    };
+
+    match command {
+        MealsCommand::Fetch {
+            restaurant,
+            date,
+            week,
+            dry_run,
+        } => {
+            let date = date.unwrap_or(today);
+            let mut meals = vec![];
+            for restaurant in restaurants(restaurant) {
+                let mut parsed = if week {
+                    meal::meal_parse_week(restaurant, date).await?
+                } else {
+                    meal::meal_parse(restaurant, date).await?
+                };
+                meals.append(&mut parsed);
+            }
+
+            if !dry_run {
+                let client = db::connect().await?;
+                meal::save_meals(&db::meal_collection(&client), &meals).await?;
+            }
+            print_meals(&meals, format)
+        }
+        MealsCommand::Today { restaurant } => {
+            let client = db::connect().await?;
+            let collection = db::meal_collection(&client);
+            let mut meals = meal::todays_meals(&collection, restaurant).await?;
+
+            if meals.is_empty() {
+                for restaurant in restaurants(restaurant) {
+                    meals.append(&mut meal::meal_parse(restaurant, today).await?);
+                }
+                meal::save_meals(&collection, &meals).await?;
+            }
+            print_meals(&meals, format)
+        }
+    }
+}
+
+async fn run_rooms(
+    command: RoomsCommand,
+    semester: Option<Semester>,
+    format: Format,
+) -> Result<()> {
+    let semester = match semester {
+        Some(semester) => semester,
+        None => Semester::configured()?,
+    };
+    let client = db::connect().await?;
+    let index = OccupancyIndex::load(&client, CourseStorage::configured()?, &semester).await?;
+
+    match command {
+        RoomsCommand::Free { building, at } => {
+            let building = find_building(&building)?;
+            let (weekday, time) = at.resolve();
+            let free = index.free_rooms(&building, weekday, time);
+            if format != Format::Table {
+                return print_items(&free, format);
+            }
+
+            println!(
+                "Free in {building} on {weekday} at {}:",
+                time.format("%H:%M")
+            );
+            for room in free {
+                match room.until {
+                    Some(until) => println!("{}\tuntil {}", room.room, until.format("%H:%M")),
+                    None => println!("{}\tfor the rest of the day", room.room),
+                }
+            }
+            Ok(())
+        }
+        RoomsCommand::Next { building, room, at } => {
+            let building = find_building(&building)?;
+            let (weekday, time) = at.resolve();
+            let next = index.next_occupied(&building, &room, weekday, time);
+            if format != Format::Table {
+                return print_items(next.as_slice(), format);
+            }
+
+            match next {
+                Some(booking) => println!(
+                    "{} {}-{}\t{} ({}-{})",
+                    booking.weekday,
+                    booking.start.format("%H:%M"),
+                    booking.end.format("%H:%M"),
+                    booking.subject_name,
+                    booking.subject_code,
+                    booking.class_number
+                ),
+                None => println!("No class in {building} {room}"),
+            }
+            Ok(())
+        }
+        RoomsCommand::Week { building, room } => {
+            let building = find_building(&building)?;
+            let week = index.week(&building, &room);
+            if format != Format::Table {
+                let bookings: Vec<_> = week.iter().flatten().collect();
+                return print_items(&bookings, format);
+            }
+
+            for (day, bookings) in week.iter().enumerate() {
+                let weekday = Weekday::try_from(day as u8).unwrap_or(Weekday::Mon);
+                let classes: Vec<String> = bookings
+                    .iter()
+                    .map(|booking| {
+                        format!(
+                            "{}-{} {}",
+                            booking.start.format("%H:%M"),
+                            booking.end.format("%H:%M"),
+                            booking.subject_name
+                        )
+                    })
+                    .collect();
+                println!("{weekday}\t{}", classes.join(", "));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Id of the building a `--building` value names.
+fn find_building(name: &str) -> Result<String> {
+    let campus = Campus::shared()?;
+    campus
+        .get(name)
+        .or_else(|| campus.resolve(name))
+        .map(|building| building.id.clone())
+        .ok_or_else(|| anyhow!("Unknown building: {name}"))
+}
+
+async fn run_courses(command: CoursesCommand, format: Format) -> Result<()> {
+    let storage = CourseStorage::configured()?;
+    match command {
+        CoursesCommand::Fetch {
+            selection,
+            concurrency,
+            dry_run,
+            force,
+        } => {
+            let semester = selection.semester()?;
+            let sinks = changelog::sinks_from_env()?;
+            let client = db::connect().await?;
+            let mut session = HaksaSession::open().await?;
+            let fetched = session
+                .fetch_categories(&semester, &selection.categories(), concurrency)
+                .await?;
+
+            let mut changes = vec![];
+            let mut failed = vec![];
+            // Printed at once, a format like Parquet holding a single table
+            let mut printed = vec![];
+            for CategoryCourses { category, courses } in fetched {
+                let courses = match courses {
+                    Ok(courses) => courses,
+                    Err(e) => {
+                        eprintln!("{category}: {e:#}");
+                        failed.push(category);
+                        continue;
+                    }
+                };
+                // Status goes to stderr, stdout being left to the courses
+                eprintln!("{category}: {} courses", courses.len());
+
+                if !dry_run {
+                    let stored = changelog::store_fetch(
+                        &client, storage, &semester, category, &courses, force,
+                    )
+                    .await;
+                    let mut stored = match stored {
+                        Ok(stored) => stored,
+                        Err(e) => {
+                            eprintln!("{category}: {e:#}");
+                            failed.push(category);
+                            continue;
+                        }
+                    };
+                    let counts = stored.counts;
+                    eprintln!(
+                        "{category}: {} new, {} updated, {} unchanged, {} removed",
+                        counts.upserted,
+                        counts.modified,
+                        counts.matched.saturating_sub(counts.modified),
+                        stored.removed
+                    );
+                    eprintln!("{category}: {} changes", stored.changes.len());
+                    changes.append(&mut stored.changes);
+                }
+                if format != Format::Table {
+                    printed.extend(courses);
+                }
+            }
+            if format != Format::Table {
+                print_items(&printed, format)?;
+            }
+
+            changelog::notify(&sinks, &changes).await?;
+            check_fetched(&failed)
+        }
+        CoursesCommand::Search {
+            keyword,
+            building,
+            selection,
+        } => {
+            let mut filter = doc! {};
+            if let Some(keyword) = keyword {
+                let regex = doc! { "$regex": keyword, "$options": "i" };
+                filter.insert(
+                    "$or",
+                    vec![
+                        doc! { "subject_korean_name": regex.clone() },
+                        doc! { "subject_english_name": regex.clone() },
+                        doc! { "subject_code": regex.clone() },
+                        doc! { "main_lecturer_name": regex },
+                    ],
+                );
+            }
+            if let Some(building) = building {
+                filter.insert("time_slots.classroom.building", find_building(&building)?);
+            }
+            let courses = find_courses(&selection, storage, filter).await?;
+            print_courses(&courses, format)
+        }
+        CoursesCommand::Check { picks, selection } => {
+            let codes: Vec<&String> = picks.iter().map(|(code, _)| code).collect();
+            let courses = find_courses(
+                &selection,
+                storage,
+                doc! { "subject_code": { "$in": codes } },
+            )
+            .await?;
+            let conflicts = timetable::find_conflicts(&timetable::find_picks(&courses, &picks)?);
+            if format != Format::Table {
+                return print_items(&conflicts, format);
+            }
+
+            if conflicts.is_empty() {
+                println!("No conflicts");
+            }
+            for conflict in conflicts {
+                println!(
+                    "{}-{} and {}-{}\t{} {}-{}",
+                    conflict.first.0,
+                    conflict.first.1,
+                    conflict.second.0,
+                    conflict.second.1,
+                    conflict.weekday,
+                    conflict.start.format("%H:%M"),
+                    conflict.end.format("%H:%M")
+                );
+            }
+            Ok(())
+        }
+        CoursesCommand::Plan {
+            subjects,
+            earliest,
+            latest,
+            free_days,
+            max_credits,
+            lecturers,
+            limit,
+            selection,
+        } => {
+            let courses = find_courses(
+                &selection,
+                storage,
+                doc! { "subject_code": { "$in": &subjects } },
+            )
+            .await?;
+            let constraints = Constraints {
+                earliest_start: earliest,
+                latest_end: latest,
+                free_days,
+                max_credits,
+                preferred_lecturers: lecturers,
+            };
+            let timetables = timetable::build_timetables(&courses, &subjects, &constraints, limit);
+            print_timetables(&timetables, format)
+        }
+        CoursesCommand::Diff { against, selection } => {
+            let semester = selection.semester()?;
+            let client = db::connect().await?;
+            let now = Utc::now();
+
+            let mut changes = vec![];
+            let mut failed = vec![];
+            if let Some(against) = against {
+                for category in selection.categories() {
+                    let before =
+                        course::stored_courses(&client, storage, &against, category).await?;
+                    let after =
+                        course::stored_courses(&client, storage, &semester, category).await?;
+                    changes.append(&mut changelog::diff_courses(
+                        &semester,
+                        Some(category),
+                        &before,
+                        &after,
+                        now,
+                    )?);
+                }
+            } else {
+                let mut session = HaksaSession::open().await?;
+                let fetched = session
+                    .fetch_categories(
+                        &semester,
+                        &selection.categories(),
+                        DEFAULT_FETCH_CONCURRENCY,
+                    )
+                    .await?;
+
+                for CategoryCourses { category, courses } in fetched {
+                    let fresh = match courses {
+                        Ok(courses) => courses,
+                        Err(e) => {
+                            eprintln!("{category}: {e:#}");
+                            failed.push(category);
+                            continue;
+                        }
+                    };
+                    let stored =
+                        course::stored_courses(&client, storage, &semester, category).await?;
+                    changes.append(&mut changelog::diff_courses(
+                        &semester,
+                        Some(category),
+                        &stored,
+                        &fresh,
+                        now,
+                    )?);
+                }
+            }
+
+            print_changes(&changes, format)?;
+            check_fetched(&failed)
+        }
+        CoursesCommand::Changes { since, selection } => {
+            let semester = selection.semester()?;
+            let since = match since {
+                Some(since) => Seoul
+                    .with_ymd_and_hms(since.year(), since.month(), since.day(), 0, 0, 0)
+                    .earliest()
+                    .ok_or_else(|| anyhow!("Invalid date {since}"))?
+                    .with_timezone(&Utc),
+                None => Utc::now() - chrono::Duration::days(7),
+            };
+
+            let client = db::connect().await?;
+            let categories = selection.categories();
+            let changes: Vec<CourseChange> = changelog::find_changes(
+                &db::course_changelog_collection(&client),
+                &semester,
+                since,
+            )
+            .await?
+            .into_iter()
+            .filter(|change| change.category.is_none_or(|c| categories.contains(&c)))
+            .collect();
+            print_changes(&changes, format)
+        }
+        CoursesCommand::Dedupe { selection } => {
+            let semester = selection.semester()?;
+            let client = db::connect().await?;
+            for category in selection.categories() {
+                let deleted =
+                    course::remove_duplicates(&client, storage, &semester, category).await?;
+                course::ensure_indexes(&client, storage, &semester, category).await?;
+                println!("{category}: {deleted} duplicates deleted");
+            }
+            Ok(())
+        }
+        CoursesCommand::Migrate { semester } => {
+            let client = db::connect().await?;
+            let migrated = course::migrate_to_unified(&client, semester.as_ref()).await?;
+            if format != Format::Table {
+                return print_items(&migrated, format);
+            }
+            for migrated in &migrated {
+                println!(
+                    "{} {}: {} courses, {} new, {} updated",
+                    migrated.semester,
+                    migrated.category,
+                    migrated.courses,
+                    migrated.counts.upserted,
+                    migrated.counts.modified
+                );
+            }
+            if storage == CourseStorage::Split {
+                println!("Set COURSE_STORAGE=unified to use the `courses` collection");
+            }
+            Ok(())
+        }
+        CoursesCommand::Logout => {
+            session::forget()?;
+            println!("Forgot the stored session");
+            Ok(())
+        }
+        CoursesCommand::Export { selection, export } => {
+            let categories = selection.categories();
+            let semester = selection.semester()?;
+
+            if export.scrape {
+                let mut session = HaksaSession::open().await?;
+                let fetched = session
+                    .fetch_categories(&semester, &categories, DEFAULT_FETCH_CONCURRENCY)
+                    .await?;
+                let mut courses = Vec::new();
+                for CategoryCourses {
+                    category,
+                    courses: parsed,
+                } in fetched
+                {
+                    courses.append(&mut parsed.with_context(|| format!("Fetching {category}"))?);
+                }
+                return export_items(&courses, &export, format);
+            }
+
+            match export_writer(&export, format)? {
+                Some(writer) => {
+                    let client = db::connect().await?;
+                    let count =
+                        export::export_courses(&client, storage, &semester, &categories, writer)
+                            .await?;
+                    eprintln!("Exported {count} courses");
+                    Ok(())
+                }
+                None => {
+                    let courses = find_courses(&selection, storage, doc! {}).await?;
+                    print_courses(&courses, format)
+                }
+            }
+        }
+    }
+}
+
+/// Fails once every category has been handled if some of them couldn't be fetched.
+fn check_fetched(failed: &[CourseCategory]) -> Result<()> {
+    if failed.is_empty() {
+        return Ok(());
+    }
+    let names: Vec<&str> = failed
+        .iter()
+        .map(|category| category.korean_name())
+        .collect();
+    Err(anyhow!(
+        "Failed to fetch {} of the categories: {}",
+        failed.len(),
+        names.join(", ")
+    ))
+}
+
+async fn find_courses(
+    selection: &CourseSelection,
+    storage: CourseStorage,
+    filter: Document,
+) -> Result<Vec<Course>> {
+    let client = db::connect().await?;
+    let mut courses = course::find_courses(
+        &client,
+        storage,
+        &selection.semester()?,
+        &selection.categories(),
+        filter,
+    )
+    .await?;
+
+    // Stored before slots were parsed
+    let campus = Campus::shared()?;
+    for course in courses.iter_mut().filter(|c| c.time_slots.is_empty()) {
+        course.time_slots = course.parse_time_slots(campus);
+    }
+    Ok(courses)
+}
+
+/// "F001-1" -> ("F001", "1")
+fn parse_pick(s: &str) -> Result<Pick> {
+    let (code, class) = s
+        .rsplit_once('-')
+        .ok_or_else(|| anyhow!("Expected SUBJECT_CODE-CLASS_NUMBER, got {s}"))?;
+    Ok((code.trim().to_string(), class.trim().to_string()))
+}
+
+/// Ids of `--department` and the departments below it, empty without `--department`.
+fn department_subtree(department: &Option<String>) -> Result<Vec<String>> {
+    let Some(id) = department else {
+        return Ok(vec![]);
+    };
+
+    let subtree = Directory::shared()?.subtree(id);
+    if subtree.is_empty() {
+        return Err(anyhow!("Unknown department: {id}"));
+    }
+    Ok(subtree.into_iter().map(String::from).collect())
+}
+
+fn filter_notices(notices: Vec<Notice>, filter: &NoticeFilter) -> Result<Vec<Notice>> {
+    let query = NoticeQuery {
+        category: filter.category.clone(),
+        departments: department_subtree(&filter.department)?,
+        ..Default::default()
+    };
+
+    Ok(notices
+        .into_iter()
+        .filter(|notice| query.matches(notice))
+        .collect())
+}
+
+fn print_departments(format: Format) -> Result<()> {
+    let directory = Directory::shared()?;
+    if format != Format::Table {
+        return print_items(directory.departments(), format);
+    }
+
+    for department in directory.departments() {
+        println!(
+            "{}\t{}\t{}",
+            department.id,
+            directory.path(&department.id).join(" < "),
+            department.aliases.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn print_buildings(format: Format) -> Result<()> {
+    let campus = Campus::shared()?;
+    if format != Format::Table {
+        return print_items(campus.buildings(), format);
+    }
+
+    for building in campus.buildings() {
+        println!(
+            "{}\t{}\t{}\t{}\t{}",
+            building.id,
+            building.code,
+            building.name,
+            building.english_name,
+            building.aliases.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn print_notices(notices: &[Notice], format: Format) -> Result<()> {
+    if format != Format::Table {
+        return print_items(notices, format);
+    }
+
+    for notice in notices {
+        println!(
+            "{}\t{}\t[{}] {} ({})\t{}",
+            notice.id, notice.date, notice.category, notice.title, notice.writer, notice.link
+        );
+    }
+    Ok(())
+}
+
+fn print_events(events: &[AcademicEvent], format: Format) -> Result<()> {
+    if format != Format::Table {
+        return print_items(events, format);
+    }
+
+    for event in events {
+        println!(
+            "{} ~ {}\t{:?}\t{}",
+            event.start, event.end, event.category, event.title
+        );
+    }
+    Ok(())
+}
+
+fn print_meals(meals: &[Meal], format: Format) -> Result<()> {
+    if format != Format::Table {
+        return print_items(meals, format);
+    }
+
+    for meal in meals {
+        println!(
+            "{}\t{}\t{:?}\t{}\t{}\t{}",
+            meal.date,
+            meal.restaurant.name(),
+            meal.time,
+            meal.corner.as_deref().unwrap_or("-"),
+            meal.menu.join(", "),
+            meal.price
+                .map_or("-".to_string(), |price| format!("{price}원"))
+        );
+    }
+    Ok(())
+}
+
+fn print_people(people: &[Person], format: Format) -> Result<()> {
+    if format != Format::Table {
+        return print_items(people, format);
+    }
+
+    let field = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+    for person in people {
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            person.name,
+            field(&person.department),
+            field(&person.position),
+            field(&person.phone),
+            field(&person.email),
+            field(&person.office)
+        );
+    }
+    Ok(())
+}
+
+fn print_courses(courses: &[Course], format: Format) -> Result<()> {
+    if format != Format::Table {
+        return print_items(courses, format);
+    }
+
+    for course in courses {
+        println!(
+            "{}-{}\t{}\t{}\t{}\t{}",
+            course.subject_code,
+            course.class_number,
+            course.subject_korean_name,
+            course.main_lecturer_name,
+            course.class_time_korean,
+            course.classroom
+        );
+    }
+    Ok(())
+}
+
+fn print_timetables(timetables: &[Timetable], format: Format) -> Result<()> {
+    if format != Format::Table {
+        return print_items(timetables, format);
+    }
+
+    if timetables.is_empty() {
+        println!("No timetable fits");
+    }
+    for (i, timetable) in timetables.iter().enumerate() {
+        print!(
+            "#{} {} credits, {} days",
+            i + 1,
+            timetable.credits,
+            timetable.days
+        );
+        if !timetable.missing.is_empty() {
+            print!(", without {}", timetable.missing.join(", "));
+        }
+        println!();
+        let courses: Vec<Course> = timetable.courses.iter().map(|c| (*c).clone()).collect();
+        print_courses(&courses, format)?;
+        println!();
+    }
+    Ok(())
+}
+
+fn print_changes(changes: &[CourseChange], format: Format) -> Result<()> {
+    if format != Format::Table {
+        return print_items(changes, format);
+    }
+
+    if changes.is_empty() {
+        println!("No changes");
+    }
+    for change in changes {
+        println!("{}", change.summary());
+    }
+    Ok(())
+}
+
+fn print_items<T: Serialize>(items: &[T], format: Format) -> Result<()> {
+    match format.export_format() {
+        Some(export_format) => {
+            let mut writer = ExportWriter::new(std::io::stdout(), export_format, None)?;
+            for item in items {
+                writer.write(item)?;
+            }
+            writer.finish()?;
+        }
+        None => println!("{}", serde_json::to_string_pretty(items)?),
+    }
+    Ok(())
+}
+
+/// Opens `--output` (or stdout) for streaming formats, `None` for table/json.
+fn export_writer(
+    export: &ExportArgs,
+    format: Format,
+) -> Result<Option<ExportWriter<Box<dyn Write + Send>>>> {
+    let Some(export_format) = format.export_format() else {
+        if export.output.is_some() || !export.fields.is_empty() {
+            return Err(anyhow!(
+                "--output and --fields need --format csv, ndjson or parquet"
+            ));
+        }
+        return Ok(None);
+    };
+
+    let output: Box<dyn Write + Send> = match &export.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(std::io::stdout()),
+    };
+    let fields = (!export.fields.is_empty()).then(|| export.fields.clone());
+
+    Ok(Some(ExportWriter::new(output, export_format, fields)?))
+}
+
+fn export_items<T: Serialize>(items: &[T], export: &ExportArgs, format: Format) -> Result<()> {
+    match export_writer(export, format)? {
+        Some(mut writer) => {
+            for item in items {
+                writer.write(item)?;
+            }
+            let count = writer.finish()?;
+            eprintln!("Exported {count} items");
+            Ok(())
+        }
+        None => print_items(items, format),
+    }
+}
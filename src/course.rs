@@ -0,0 +1,1280 @@
+use crate::building::Campus;
+use crate::http::HttpClient;
+use crate::timetable::{self, TimeSlot};
+use crate::{db, session};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use chrono_tz::Asia::Seoul;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use mongodb::bson::{doc, to_bson, to_document, Bson, Document};
+use mongodb::options::{FindOptions, IndexOptions};
+use mongodb::{Client, Collection, IndexModel};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use tokio::time::Duration;
+
+/// Categories fetched at once by [`fetch_categories`] unless told otherwise.
+pub const DEFAULT_FETCH_CONCURRENCY: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CourseCategory {
+    /// 전공과목 전체
+    Major,
+    /// 교양과목 전체
+    LiberalArts,
+    /// 기초과목 공통
+    Basic,
+    /// 공학기초 전체
+    EngineeringBasic,
+    /// 영역별교양 전체
+    AreaLiberalArts,
+    /// 학점교류 전체
+    CreditExchange,
+    /// 일선과목 전체
+    GeneralElective,
+}
+
+impl CourseCategory {
+    /// Every category, in the order they're fetched.
+    pub const ALL: [CourseCategory; 7] = [
+        CourseCategory::Major,
+        CourseCategory::LiberalArts,
+        CourseCategory::Basic,
+        CourseCategory::EngineeringBasic,
+        CourseCategory::AreaLiberalArts,
+        CourseCategory::CreditExchange,
+        CourseCategory::GeneralElective,
+    ];
+
+    /// `strSubmattFg` code of the course API
+    pub fn code(self) -> &'static str {
+        match self {
+            CourseCategory::Major => "U0209001",
+            CourseCategory::LiberalArts => "U0209002",
+            CourseCategory::Basic => "U0209003",
+            CourseCategory::EngineeringBasic => "U0209004",
+            CourseCategory::AreaLiberalArts => "U0209005",
+            CourseCategory::CreditExchange => "U0209006",
+            CourseCategory::GeneralElective => "U0209029",
+        }
+    }
+
+    /// Also the suffix of its `course_<semester>_<category>` collection.
+    pub fn korean_name(self) -> &'static str {
+        match self {
+            CourseCategory::Major => "전공과목",
+            CourseCategory::LiberalArts => "교양과목",
+            CourseCategory::Basic => "기초과목",
+            CourseCategory::EngineeringBasic => "공학기초",
+            CourseCategory::AreaLiberalArts => "영역별교양",
+            CourseCategory::CreditExchange => "학점교류",
+            CourseCategory::GeneralElective => "일선과목",
+        }
+    }
+
+    pub fn english_name(self) -> &'static str {
+        match self {
+            CourseCategory::Major => "Major",
+            CourseCategory::LiberalArts => "Liberal Arts",
+            CourseCategory::Basic => "Basic",
+            CourseCategory::EngineeringBasic => "Engineering Basic",
+            CourseCategory::AreaLiberalArts => "Area Liberal Arts",
+            CourseCategory::CreditExchange => "Credit Exchange",
+            CourseCategory::GeneralElective => "General Elective",
+        }
+    }
+}
+
+impl fmt::Display for CourseCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.korean_name())
+    }
+}
+
+impl FromStr for CourseCategory {
+    type Err = anyhow::Error;
+
+    /// Takes the code, the Korean or the English name, e.g. "U0209001", "전공과목", "major".
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let key = |name: &str| name.to_ascii_lowercase().replace([' ', '_', '-'], "");
+        CourseCategory::ALL
+            .into_iter()
+            .find(|category| {
+                category.code() == s
+                    || category.korean_name() == s
+                    || key(category.english_name()) == key(s)
+            })
+            .ok_or_else(|| anyhow!("Unknown course category: {s}"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Term {
+    /// 1학기
+    First,
+    /// 여름계절
+    Summer,
+    /// 2학기
+    Second,
+    /// 겨울계절
+    Winter,
+}
+
+impl Term {
+    /// `strShtmCd` code of the course API
+    pub fn code(self) -> &'static str {
+        match self {
+            Term::First => "U0002001",
+            Term::Summer => "U0002002",
+            Term::Second => "U0002003",
+            Term::Winter => "U0002004",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Term::First => "1학기",
+            Term::Summer => "여름계절",
+            Term::Second => "2학기",
+            Term::Winter => "겨울계절",
+        }
+    }
+}
+
+/// A school year and term, written "2023-1", "2023-summer", "2023-2" or "2023-winter".
+///
+/// The winter term of 2023 is the one starting in December 2023.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Semester {
+    pub year: i32,
+    pub term: Term,
+}
+
+impl Semester {
+    pub fn new(year: i32, term: Term) -> Self {
+        Semester { year, term }
+    }
+
+    /// The regular semester going on at `date`, or the next one during a break:
+    /// 1학기 from January to June, 2학기 from July to December.
+    pub fn current(date: NaiveDate) -> Self {
+        let term = if date.month() <= 6 {
+            Term::First
+        } else {
+            Term::Second
+        };
+        Semester::new(date.year(), term)
+    }
+
+    /// `COURSE_SEMESTER` if set, the [`Semester::current`] one (KST) otherwise.
+    pub fn configured() -> Result<Self> {
+        match std::env::var("COURSE_SEMESTER") {
+            Ok(semester) if !semester.trim().is_empty() => semester.parse(),
+            _ => Ok(Semester::current(
+                Utc::now().with_timezone(&Seoul).date_naive(),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Semester {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let term = match self.term {
+            Term::First => "1",
+            Term::Summer => "summer",
+            Term::Second => "2",
+            Term::Winter => "winter",
+        };
+        write!(f, "{}-{term}", self.year)
+    }
+}
+
+impl FromStr for Semester {
+    type Err = anyhow::Error;
+
+    /// Also takes the Korean names, e.g. "2023-1학기", "2023-여름계절", "2023-w".
+    fn from_str(s: &str) -> Result<Self> {
+        let (year, term) = s
+            .trim()
+            .split_once('-')
+            .ok_or_else(|| anyhow!("Semester should look like 2023-1, got {s}"))?;
+        let year = year
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("Unknown year in semester {s}"))?;
+        let term = match term.trim().to_ascii_lowercase().as_str() {
+            "1" | "1학기" => Term::First,
+            "s" | "summer" | "여름" | "여름계절" => Term::Summer,
+            "2" | "2학기" => Term::Second,
+            "w" | "winter" | "겨울" | "겨울계절" => Term::Winter,
+            _ => return Err(anyhow!("Unknown term in semester {s} (1|summer|2|winter)")),
+        };
+        Ok(Semester::new(year, term))
+    }
+}
+
+pub fn get_collection_name(semester: &Semester, category: CourseCategory) -> String {
+    format!("course_{}_{}", semester, category)
+}
+
+/// The semester and category of a `course_<semester>_<category>` collection.
+pub fn parse_collection_name(name: &str) -> Option<(Semester, CourseCategory)> {
+    let (semester, category) = name.strip_prefix("course_")?.rsplit_once('_')?;
+    Some((semester.parse().ok()?, category.parse().ok()?))
+}
+
+/// How stored courses are laid out, `COURSE_STORAGE` choosing one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CourseStorage {
+    /// A `course_<semester>_<category>` collection per category, a section listed under
+    /// several categories being stored in each
+    #[default]
+    Split,
+    /// Every section once in the `courses` collection, with its `semester` and the
+    /// `categories` it's listed under
+    Unified,
+}
+
+impl CourseStorage {
+    /// `COURSE_STORAGE` (`split` or `unified`), split by default.
+    pub fn configured() -> Result<Self> {
+        match std::env::var("COURSE_STORAGE") {
+            Ok(storage) if !storage.trim().is_empty() => storage.parse(),
+            _ => Ok(CourseStorage::Split),
+        }
+    }
+
+    fn collection(
+        self,
+        client: &Client,
+        semester: &Semester,
+        category: CourseCategory,
+    ) -> Collection<Course> {
+        match self {
+            CourseStorage::Split => db::course_collection(client, semester, category),
+            CourseStorage::Unified => db::courses_collection(client),
+        }
+    }
+
+    /// The collections to query for the courses of `categories`, and the filter to query them
+    /// with.
+    pub fn sources(
+        self,
+        client: &Client,
+        semester: &Semester,
+        categories: &[CourseCategory],
+    ) -> Result<Vec<(Collection<Course>, Document)>> {
+        Ok(match self {
+            CourseStorage::Split => categories
+                .iter()
+                .map(|category| {
+                    let filter = doc! { "removed_at": null };
+                    (db::course_collection(client, semester, *category), filter)
+                })
+                .collect(),
+            CourseStorage::Unified => {
+                let filter = doc! {
+                    "semester": semester.to_string(),
+                    "categories": { "$in": to_bson(categories)? },
+                };
+                vec![(db::courses_collection(client), filter)]
+            }
+        })
+    }
+}
+
+impl FromStr for CourseStorage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "split" => Ok(CourseStorage::Split),
+            "unified" => Ok(CourseStorage::Unified),
+            _ => Err(anyhow!("Unknown course storage {s} (split|unified)")),
+        }
+    }
+}
+
+/// What identifies a section: (year, semester, subject_code, class_number).
+const KEY_FIELDS: [&str; 4] = ["year", "semester_code", "subject_code", "class_number"];
+
+/// Upserts sent per `update` command.
+const WRITE_BATCH: usize = 500;
+
+/// What [`save_courses`] did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SaveCounts {
+    /// Sections already stored
+    pub matched: u64,
+    /// Stored sections that changed
+    pub modified: u64,
+    /// New sections
+    pub upserted: u64,
+}
+
+/// Stored courses of `semester` matching `filter`, listed under any of `categories`.
+///
+/// With [`CourseStorage::Split`], a section listed under several of them is returned for each.
+pub async fn find_courses(
+    client: &Client,
+    storage: CourseStorage,
+    semester: &Semester,
+    categories: &[CourseCategory],
+    filter: Document,
+) -> Result<Vec<Course>> {
+    let mut courses = vec![];
+    for (collection, mut query) in storage.sources(client, semester, categories)? {
+        query.extend(filter.clone());
+        let mut found: Vec<Course> = collection.find(query, None).await?.try_collect().await?;
+        courses.append(&mut found);
+    }
+    Ok(courses)
+}
+
+/// Stored courses of a category.
+pub async fn stored_courses(
+    client: &Client,
+    storage: CourseStorage,
+    semester: &Semester,
+    category: CourseCategory,
+) -> Result<Vec<Course>> {
+    find_courses(client, storage, semester, &[category], doc! {}).await
+}
+
+/// The unique index on [`KEY_FIELDS`], and the one on semester and categories of the unified
+/// collection.
+pub async fn ensure_indexes(
+    client: &Client,
+    storage: CourseStorage,
+    semester: &Semester,
+    category: CourseCategory,
+) -> Result<()> {
+    let keys: Document = KEY_FIELDS
+        .iter()
+        .map(|field| (field.to_string(), Bson::Int32(1)))
+        .collect();
+    let mut indexes = vec![IndexModel::builder()
+        .keys(keys)
+        .options(
+            IndexOptions::builder()
+                .unique(true)
+                .name("section".to_string())
+                .build(),
+        )
+        .build()];
+    if storage == CourseStorage::Unified {
+        indexes.push(
+            IndexModel::builder()
+                .keys(doc! { "semester": 1, "categories": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .name("semester_categories".to_string())
+                        .build(),
+                )
+                .build(),
+        );
+    }
+
+    let collection = storage.collection(client, semester, category);
+    collection
+        .create_indexes(indexes, None)
+        .await
+        .map_err(|e| {
+            anyhow!(
+                "Indexing {}: {e} (stored twice? run `ajou courses dedupe`)",
+                collection.name()
+            )
+        })?;
+    Ok(())
+}
+
+/// Upserts `courses` by [`KEY_FIELDS`], in batches of unordered updates.
+///
+/// With [`CourseStorage::Unified`], `category` is added to the categories of each section.
+pub async fn save_courses(
+    client: &Client,
+    storage: CourseStorage,
+    semester: &Semester,
+    category: CourseCategory,
+    courses: &[Course],
+) -> Result<SaveCounts> {
+    ensure_indexes(client, storage, semester, category).await?;
+
+    let collection = storage.collection(client, semester, category);
+    let database = client.database(db::DATABASE_NAME);
+    let mut counts = SaveCounts::default();
+
+    for batch in courses.chunks(WRITE_BATCH) {
+        let updates = batch
+            .iter()
+            .map(|course| upsert_statement(course, storage, semester, category))
+            .collect::<Result<Vec<Document>>>()?;
+
+        // The driver has no bulk write before MongoDB 8, the `update` command takes many updates
+        let reply = database
+            .run_command(
+                doc! { "update": collection.name(), "updates": updates, "ordered": false },
+                None,
+            )
+            .await?;
+
+        if let Ok(errors) = reply.get_array("writeErrors") {
+            let first = errors
+                .first()
+                .and_then(Bson::as_document)
+                .and_then(|error| error.get_str("errmsg").ok())
+                .unwrap_or_default();
+            return Err(anyhow!(
+                "{} of {} courses not saved in {}: {first}",
+                errors.len(),
+                batch.len(),
+                collection.name()
+            ));
+        }
+
+        let count = |field: &str| match reply.get(field) {
+            Some(Bson::Int32(n)) => *n as u64,
+            Some(Bson::Int64(n)) => *n as u64,
+            _ => 0,
+        };
+        let upserted = reply
+            .get_array("upserted")
+            .map_or(0, |ids| ids.len() as u64);
+        counts.upserted += upserted;
+        counts.matched += count("n").saturating_sub(upserted);
+        counts.modified += count("nModified");
+    }
+
+    Ok(counts)
+}
+
+/// An `update` command statement setting a course, matched by [`KEY_FIELDS`].
+fn upsert_statement(
+    course: &Course,
+    storage: CourseStorage,
+    semester: &Semester,
+    category: CourseCategory,
+) -> Result<Document> {
+    let mut document = to_document(course)?;
+    let filter = section_filter(&document);
+
+    Ok(match storage {
+        CourseStorage::Split => doc! {
+            "q": filter,
+            // Listed again after a fetch left it out
+            "u": { "$set": document, "$unset": { "removed_at": "" } },
+            "upsert": true,
+        },
+        CourseStorage::Unified => {
+            // Merged with the categories the section is already stored under
+            document.remove("categories");
+            document.insert("semester", semester.to_string());
+            doc! {
+                "q": filter,
+                "u": {
+                    "$set": document,
+                    "$addToSet": { "categories": to_bson(&category)? },
+                    "$unset": { "removed_at": "" },
+                },
+                "upsert": true,
+            }
+        }
+    })
+}
+
+/// The [`KEY_FIELDS`] of a serialized course.
+fn section_filter(document: &Document) -> Document {
+    KEY_FIELDS
+        .iter()
+        .map(|field| {
+            let value = document.get(*field).cloned().unwrap_or(Bson::Null);
+            (field.to_string(), value)
+        })
+        .collect()
+}
+
+/// Takes stored `courses` out of `category`, e.g. sections a fetch no longer lists, returning
+/// how many were taken out.
+///
+/// Nothing is deleted: a section is marked with `removed_at`, a unified one once it's listed
+/// under no category anymore.
+pub async fn remove_courses(
+    client: &Client,
+    storage: CourseStorage,
+    semester: &Semester,
+    category: CourseCategory,
+    courses: &[&Course],
+    now: DateTime<Utc>,
+) -> Result<u64> {
+    if courses.is_empty() {
+        return Ok(0);
+    }
+    let sections = courses
+        .iter()
+        .map(|course| Ok(section_filter(&to_document(course)?)))
+        .collect::<Result<Vec<Document>>>()?;
+
+    match storage {
+        CourseStorage::Split => Ok(db::course_collection(client, semester, category)
+            .update_many(
+                doc! { "$or": sections },
+                doc! { "$set": { "removed_at": to_bson(&now)? } },
+                None,
+            )
+            .await?
+            .modified_count),
+        CourseStorage::Unified => {
+            let collection = db::courses_collection(client);
+            let semester = semester.to_string();
+            let pulled = collection
+                .update_many(
+                    doc! { "semester": &semester, "$or": sections },
+                    doc! { "$pull": { "categories": to_bson(&category)? } },
+                    None,
+                )
+                .await?
+                .modified_count;
+            collection
+                .update_many(
+                    doc! {
+                        "semester": semester,
+                        "categories": { "$size": 0 },
+                        "removed_at": null,
+                    },
+                    doc! { "$set": { "removed_at": to_bson(&now)? } },
+                    None,
+                )
+                .await?;
+            Ok(pulled)
+        }
+    }
+}
+
+/// Deletes all but the latest stored copy of each section, returning how many were deleted.
+///
+/// Needed once for collections written before sections were keyed by [`KEY_FIELDS`].
+pub async fn remove_duplicates(
+    client: &Client,
+    storage: CourseStorage,
+    semester: &Semester,
+    category: CourseCategory,
+) -> Result<u64> {
+    let collection = storage
+        .collection(client, semester, category)
+        .clone_with_type::<Document>();
+    let options = FindOptions::builder().sort(doc! { "_id": -1 }).build();
+    let documents: Vec<Document> = collection
+        .find(doc! {}, options)
+        .await?
+        .try_collect()
+        .await?;
+
+    let mut seen = std::collections::HashSet::new();
+    let mut duplicates = vec![];
+    for document in documents {
+        let key: Vec<String> = KEY_FIELDS
+            .iter()
+            .map(|field| {
+                document
+                    .get(*field)
+                    .map(Bson::to_string)
+                    .unwrap_or_default()
+            })
+            .collect();
+        if !seen.insert(key) {
+            if let Some(id) = document.get("_id") {
+                duplicates.push(id.clone());
+            }
+        }
+    }
+
+    if duplicates.is_empty() {
+        return Ok(0);
+    }
+    Ok(collection
+        .delete_many(doc! { "_id": { "$in": duplicates } }, None)
+        .await?
+        .deleted_count)
+}
+
+/// A `course_<semester>_<category>` collection copied into the unified one.
+#[derive(Debug, Clone, Serialize)]
+pub struct Migrated {
+    pub semester: Semester,
+    pub category: CourseCategory,
+    pub courses: usize,
+    pub counts: SaveCounts,
+}
+
+/// Copies the `course_<semester>_<category>` collections (of `semester`, every one otherwise)
+/// into the unified `courses` collection, leaving them in place.
+///
+/// Sections listed under several categories are stored once, with all of them. Running it
+/// again only updates what changed since.
+pub async fn migrate_to_unified(
+    client: &Client,
+    semester: Option<&Semester>,
+) -> Result<Vec<Migrated>> {
+    let mut collections: Vec<(Semester, CourseCategory)> = client
+        .database(db::DATABASE_NAME)
+        .list_collection_names(None)
+        .await?
+        .iter()
+        .filter_map(|name| parse_collection_name(name))
+        .filter(|(found, _)| semester.is_none_or(|semester| semester == found))
+        .collect();
+    collections.sort();
+
+    let mut migrated = vec![];
+    for (semester, category) in collections {
+        let courses = stored_courses(client, CourseStorage::Split, &semester, category).await?;
+        let counts = save_courses(
+            client,
+            CourseStorage::Unified,
+            &semester,
+            category,
+            &courses,
+        )
+        .await?;
+        migrated.push(Migrated {
+            semester,
+            category,
+            courses: courses.len(),
+            counts,
+        });
+    }
+    Ok(migrated)
+}
+
+// Course
+#[derive(Debug, Deserialize, Default)]
+pub struct VariableList {
+    #[serde(rename = "ErrorMsg")]
+    pub error_msg: String,
+    #[serde(rename = "ErrorCode")]
+    pub error_code: String,
+}
+
+impl VariableList {
+    /// Anything but "" or "0" means the request was refused, mostly for an expired session.
+    pub fn session_expired(&self) -> bool {
+        !matches!(self.error_code.trim(), "" | "0")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct Course {
+    #[serde(rename(deserialize = "tm", serialize = "duration"), alias = "duration")]
+    pub duration: f32,
+
+    #[serde(
+        rename(deserialize = "submattFgEngNm", serialize = "course_type"),
+        alias = "course_type"
+    )]
+    pub course_type: Option<String>,
+
+    #[serde(
+        rename(deserialize = "ltTmEngNm", serialize = "class_time"),
+        alias = "class_time"
+    )]
+    pub class_time: String,
+
+    #[serde(
+        rename(deserialize = "clssNo", serialize = "class_number"),
+        alias = "class_number"
+    )]
+    pub class_number: String,
+
+    #[serde(
+        rename(deserialize = "rcomShyrCdNm", serialize = "recommended_year"),
+        default,
+        alias = "recommended_year"
+    )]
+    pub recommended_year: Option<String>,
+
+    #[serde(
+        rename(deserialize = "sustLsnFgNm", serialize = "course_category"),
+        alias = "course_category"
+    )]
+    pub course_category: String,
+
+    #[serde(
+        rename(
+            deserialize = "maLecturerEmplNo",
+            serialize = "main_lecturer_employee_number"
+        ),
+        alias = "main_lecturer_employee_number"
+    )]
+    pub main_lecturer_employee_number: String,
+
+    #[serde(
+        rename(
+            deserialize = "abeekInspPracPntCnt",
+            serialize = "abeek_practical_points"
+        ),
+        alias = "abeek_practical_points"
+    )]
+    pub abeek_practical_points: f32,
+
+    #[serde(
+        rename(deserialize = "fileNm", serialize = "file_name"),
+        default,
+        alias = "file_name"
+    )]
+    pub file_name: Option<String>,
+
+    #[serde(
+        rename(deserialize = "maLecturerEmplNm", serialize = "main_lecturer_name"),
+        alias = "main_lecturer_name"
+    )]
+    pub main_lecturer_name: String,
+
+    #[serde(
+        rename(deserialize = "sustLsnFgEngNm", serialize = "course_category_english"),
+        alias = "course_category_english"
+    )]
+    pub course_category_english: String,
+
+    #[serde(
+        rename(deserialize = "mjCdEngNm", serialize = "major_code_english"),
+        default,
+        alias = "major_code_english"
+    )]
+    pub major_code_english: Option<String>,
+
+    #[serde(
+        rename(deserialize = "sustCd", serialize = "department_code"),
+        alias = "department_code"
+    )]
+    pub department_code: String,
+
+    #[serde(
+        rename(deserialize = "planInputYn", serialize = "plan_input_status"),
+        alias = "plan_input_status"
+    )]
+    pub plan_input_status: String,
+
+    #[serde(
+        rename(deserialize = "filePath", serialize = "file_path"),
+        default,
+        alias = "file_path"
+    )]
+    pub file_path: Option<String>,
+
+    #[serde(
+        rename(
+            deserialize = "abeekTheoPntCnt",
+            serialize = "abeek_theoretical_points"
+        ),
+        alias = "abeek_theoretical_points"
+    )]
+    pub abeek_theoretical_points: f32,
+
+    #[serde(
+        rename(deserialize = "ltRoomEngNm", serialize = "classroom_english"),
+        alias = "classroom_english"
+    )]
+    pub classroom_english: String,
+
+    #[serde(
+        rename(deserialize = "emplNo", serialize = "employee_number"),
+        alias = "employee_number"
+    )]
+    pub employee_number: String,
+
+    #[serde(
+        rename(deserialize = "sustCdEngNm", serialize = "department_english"),
+        default,
+        alias = "department_english"
+    )]
+    pub department_english: Option<String>,
+
+    #[serde(
+        rename(deserialize = "submattFgNm", serialize = "course_type_korean"),
+        alias = "course_type_korean"
+    )]
+    pub course_type_korean: String,
+
+    #[serde(
+        rename(deserialize = "sbjtCd", serialize = "subject_code"),
+        alias = "subject_code"
+    )]
+    pub subject_code: String,
+
+    #[serde(
+        rename(deserialize = "mainOpenLtNo", serialize = "main_open_course_number"),
+        alias = "main_open_course_number"
+    )]
+    pub main_open_course_number: String,
+
+    #[serde(
+        rename(deserialize = "mjCd", serialize = "major_code"),
+        alias = "major_code"
+    )]
+    pub major_code: String,
+
+    #[serde(
+        rename(deserialize = "mjCdNm", serialize = "major_name"),
+        default,
+        alias = "major_name"
+    )]
+    pub major_name: Option<String>,
+
+    #[serde(
+        rename(deserialize = "ltRoomNm", serialize = "classroom"),
+        alias = "classroom"
+    )]
+    pub classroom: String,
+
+    #[serde(
+        rename(deserialize = "abeePnt", serialize = "abee_point"),
+        alias = "abee_point"
+    )]
+    pub abee_point: Option<f32>,
+
+    #[serde(
+        rename(deserialize = "shtmCd", serialize = "semester_code"),
+        alias = "semester_code"
+    )]
+    pub semester_code: String,
+
+    #[serde(
+        rename(
+            deserialize = "maLecturerEmplEngNm",
+            serialize = "main_lecturer_english_name"
+        ),
+        alias = "main_lecturer_english_name"
+    )]
+    pub main_lecturer_english_name: Option<String>,
+
+    #[serde(
+        rename(deserialize = "sustLsnFg", serialize = "course_category_code"),
+        alias = "course_category_code"
+    )]
+    pub course_category_code: String,
+
+    #[serde(
+        rename(deserialize = "openLtNo", serialize = "open_course_number"),
+        alias = "open_course_number"
+    )]
+    pub open_course_number: String,
+
+    #[serde(
+        rename(deserialize = "orgLangLtYn", serialize = "original_language_course"),
+        alias = "original_language_course"
+    )]
+    pub original_language_course: Option<String>,
+
+    #[serde(
+        rename(deserialize = "cqiYn", serialize = "cqi_status"),
+        alias = "cqi_status"
+    )]
+    pub cqi_status: String,
+
+    #[serde(
+        rename(deserialize = "lsnApprDetailPop", serialize = "course_evaluation"),
+        alias = "course_evaluation"
+    )]
+    pub course_evaluation: String,
+
+    #[serde(
+        rename(deserialize = "shtmNm", serialize = "semester_name"),
+        alias = "semester_name"
+    )]
+    pub semester_name: String,
+
+    #[serde(rename(deserialize = "yy", serialize = "year"), alias = "year")]
+    pub year: String,
+
+    #[serde(
+        rename(deserialize = "sustCdNm", serialize = "department_name"),
+        alias = "department_name"
+    )]
+    pub department_name: Option<String>,
+
+    #[serde(
+        rename(deserialize = "engGrdFgNm", serialize = "english_grade_type"),
+        default,
+        alias = "english_grade_type"
+    )]
+    pub english_grade_type: Option<String>,
+
+    #[serde(
+        rename(deserialize = "abeekDgnPntCnt", serialize = "abeek_design_points"),
+        alias = "abeek_design_points"
+    )]
+    pub abeek_design_points: f32,
+
+    #[serde(
+        rename(deserialize = "abeekYn", serialize = "abeek_status"),
+        alias = "abeek_status"
+    )]
+    pub abeek_status: String,
+
+    #[serde(
+        rename(deserialize = "fileFg", serialize = "file_status"),
+        alias = "file_status"
+    )]
+    pub file_status: String,
+
+    #[serde(
+        rename(deserialize = "sbjtKorNm", serialize = "subject_korean_name"),
+        alias = "subject_korean_name"
+    )]
+    pub subject_korean_name: String,
+
+    #[serde(
+        rename(
+            deserialize = "lsnPdocMngtClssYn",
+            serialize = "lesson_document_management_class"
+        ),
+        default,
+        alias = "lesson_document_management_class"
+    )]
+    pub lesson_document_management_class: Option<String>,
+
+    #[serde(
+        rename(deserialize = "ltTmNm", serialize = "class_time_korean"),
+        alias = "class_time_korean"
+    )]
+    pub class_time_korean: String,
+
+    #[serde(
+        rename(deserialize = "rcomShyrCd", serialize = "recommended_year_code"),
+        default,
+        alias = "recommended_year_code"
+    )]
+    pub recommended_year_code: Option<String>,
+
+    #[serde(
+        rename(deserialize = "tlsnNo", serialize = "lesson_number"),
+        alias = "lesson_number"
+    )]
+    pub lesson_number: String,
+
+    #[serde(
+        rename(deserialize = "apprUnAdptYn", serialize = "approved_unadapted"),
+        alias = "approved_unadapted"
+    )]
+    pub approved_unadapted: String,
+
+    #[serde(
+        rename(deserialize = "pnt", serialize = "credit_points"),
+        alias = "credit_points"
+    )]
+    pub credit_points: f32,
+
+    #[serde(
+        rename(deserialize = "sbjtId", serialize = "subject_id"),
+        alias = "subject_id"
+    )]
+    pub subject_id: String,
+
+    #[serde(
+        rename(deserialize = "sbjtEngNm", serialize = "subject_english_name"),
+        alias = "subject_english_name"
+    )]
+    pub subject_english_name: String,
+
+    #[serde(
+        rename(deserialize = "coopOpenLtYn", serialize = "cooperative_open_course"),
+        alias = "cooperative_open_course"
+    )]
+    pub cooperative_open_course: String,
+
+    #[serde(
+        rename(deserialize = "coopLt", serialize = "cooperative_course"),
+        default,
+        alias = "cooperative_course"
+    )]
+    pub cooperative_course: Option<String>,
+
+    #[serde(
+        rename(deserialize = "rowStatus", serialize = "row_status"),
+        alias = "row_status"
+    )]
+    pub row_status: i32,
+
+    #[serde(
+        rename(deserialize = "ltFgNm", serialize = "lecture_type_name"),
+        default,
+        alias = "lecture_type_name"
+    )]
+    pub lecture_type_name: Option<String>,
+
+    /// Parsed from `class_time_korean` (or `class_time`) when fetched
+    #[serde(default)]
+    pub time_slots: Vec<TimeSlot>,
+    /// e.g. "2023-1", set in the unified `courses` collection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub semester: Option<String>,
+
+    /// Categories the section is listed under, set in the unified `courses` collection
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<CourseCategory>,
+
+    /// When a fetch stopped listing the section, which is then left out of queries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removed_at: Option<DateTime<Utc>>,
+}
+
+impl Course {
+    /// Weekly slots of the course, in `classroom` when the time doesn't say where, with their
+    /// rooms resolved to the buildings of `campus`.
+    pub fn parse_time_slots(&self, campus: &Campus) -> Vec<TimeSlot> {
+        let mut slots = timetable::parse_class_time(&self.class_time_korean);
+        if slots.is_empty() {
+            slots = timetable::parse_class_time(&self.class_time);
+        }
+
+        let classroom = self.classroom.trim();
+        if !classroom.is_empty() && !classroom.contains(',') && !timetable::is_online(classroom) {
+            for slot in slots
+                .iter_mut()
+                .filter(|slot| slot.room.is_none() && !slot.online)
+            {
+                slot.room = Some(classroom.to_string());
+            }
+        }
+        for slot in &mut slots {
+            slot.classroom = slot
+                .room
+                .as_deref()
+                .and_then(|room| campus.parse_classroom(room));
+        }
+        slots
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct DatasetList {
+    #[serde(rename = "DS_COUR120")]
+    pub ds_cour120: Vec<Course>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+
+pub struct CourseResp {
+    #[serde(rename = "VariableList")]
+    pub var_list: VariableList,
+    /// Left out when the request is refused
+    #[serde(rename = "DatasetList", default)]
+    pub data_list: DatasetList,
+}
+
+pub async fn course_parse(
+    semester: &Semester,
+    category: CourseCategory,
+    jsession: &str,
+) -> Result<CourseResp> {
+    eprintln!("Course parse: {} {}", semester, category);
+    let payload = serde_json::json!({
+        "url": "uni/uni/cour/lssn/findCourLecturePlanDocumentReg.action",
+        "param": {
+            "strYy": semester.year.to_string(),
+            "strShtmCd": semester.term.code(),
+            "strSubmattFg": category.code(),
+            "strSustcd": "",
+            "strMjCd": "",
+            "strSubmattFldFg": "",
+            "strCoopOpenYn": "공동개설"
+        }
+    });
+
+    let res = HttpClient::shared()?
+        .post_json(
+            &std::env::var("COURSE").map_err(|_| anyhow!("COURSE must be set"))?,
+            session::haksa_headers(jsession)?,
+            &payload,
+            Duration::from_secs(10),
+            |body| {
+                serde_json::from_str::<CourseResp>(body)
+                    .is_ok_and(|courses| !courses.data_list.ds_cour120.is_empty())
+            },
+        )
+        .await?;
+
+    let courses: Result<CourseResp, serde_json::Error> = serde_json::from_str(&res.body);
+    match courses {
+        Ok(courses) if courses.var_list.session_expired() => Err(anyhow!(
+            "Request refused ({} {}), session expired?",
+            courses.var_list.error_code,
+            courses.var_list.error_msg
+        )),
+        Ok(mut courses) => {
+            let campus = Campus::shared()?;
+            for course in &mut courses.data_list.ds_cour120 {
+                // Part of the key the courses are stored by
+                if course.year.is_empty() {
+                    course.year = semester.year.to_string();
+                }
+                if course.semester_code.is_empty() {
+                    course.semester_code = semester.term.code().to_string();
+                }
+                course.time_slots = course.parse_time_slots(campus);
+            }
+            Ok(courses)
+        }
+        // e.g. the SSO login page, which must not pass for a category without courses
+        Err(e) => Err(anyhow!(
+            "Unexpected course response, session expired? ({e})"
+        )),
+    }
+}
+
+/// Courses of one category, or why they couldn't be fetched.
+#[derive(Debug)]
+pub struct CategoryCourses {
+    pub category: CourseCategory,
+    pub courses: Result<Vec<Course>>,
+}
+
+/// Fetches `categories` of `semester`, at most `concurrency` at a time.
+///
+/// Results come back in the order of `categories`, a failed category not stopping the others.
+pub async fn fetch_categories(
+    semester: &Semester,
+    categories: &[CourseCategory],
+    jsession: &str,
+    concurrency: usize,
+) -> Vec<CategoryCourses> {
+    stream::iter(categories.iter().copied())
+        .map(|category| async move {
+            let courses = course_parse(semester, category, jsession)
+                .await
+                .map(|resp| resp.data_list.ds_cour120);
+            CategoryCourses { category, courses }
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+#[tokio::test]
+async fn course_test() {
+    let semester = Semester::configured().unwrap();
+    let course = course_parse(&semester, CourseCategory::AreaLiberalArts, "")
+        .await
+        .unwrap();
+
+    println!("{:#?}", course.data_list.ds_cour120[0]);
+}
+
+#[test]
+fn test_semester() {
+    let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+    assert_eq!(
+        Semester::current(date(2023, 2, 20)),
+        Semester::new(2023, Term::First)
+    );
+    assert_eq!(
+        Semester::current(date(2023, 7, 10)),
+        Semester::new(2023, Term::Second)
+    );
+
+    let semester: Semester = "2023-1".parse().unwrap();
+    assert_eq!(semester.term.code(), "U0002001");
+    assert_eq!(
+        get_collection_name(&semester, CourseCategory::Major),
+        "course_2023-1_전공과목"
+    );
+
+    let winter: Semester = "2023-겨울계절".parse().unwrap();
+    assert_eq!(winter, Semester::new(2023, Term::Winter));
+    assert_eq!(winter.to_string(), "2023-winter");
+    assert_eq!(winter.to_string().parse::<Semester>().unwrap(), winter);
+    assert!(Semester::new(2023, Term::Summer) < Semester::new(2023, Term::Second));
+
+    assert!("2023".parse::<Semester>().is_err());
+    assert!("2023-3".parse::<Semester>().is_err());
+}
+
+#[test]
+fn test_course_category() {
+    for category in CourseCategory::ALL {
+        assert_eq!(category.code().parse::<CourseCategory>().unwrap(), category);
+        assert_eq!(
+            category.korean_name().parse::<CourseCategory>().unwrap(),
+            category
+        );
+        assert_eq!(
+            category.english_name().parse::<CourseCategory>().unwrap(),
+            category
+        );
+    }
+    assert_eq!(
+        "engineering-basic".parse::<CourseCategory>().unwrap(),
+        CourseCategory::EngineeringBasic
+    );
+    assert_eq!(CourseCategory::GeneralElective.code(), "U0209029");
+    assert!("U0209099".parse::<CourseCategory>().is_err());
+}
+
+#[test]
+fn test_upsert_statement() {
+    let course = Course {
+        year: "2023".to_string(),
+        semester_code: "U0002001".to_string(),
+        subject_code: "F001".to_string(),
+        subject_id: "F001-2023".to_string(),
+        class_number: "2".to_string(),
+        ..Default::default()
+    };
+    let semester: Semester = "2023-1".parse().unwrap();
+    let statement = upsert_statement(
+        &course,
+        CourseStorage::Split,
+        &semester,
+        CourseCategory::Major,
+    )
+    .unwrap();
+
+    assert_eq!(
+        statement.get_document("q").unwrap(),
+        &doc! {
+            "year": "2023",
+            "semester_code": "U0002001",
+            "subject_code": "F001",
+            "class_number": "2",
+        }
+    );
+    assert!(statement.get_bool("upsert").unwrap());
+    let set = statement
+        .get_document("u")
+        .and_then(|update| update.get_document("$set"))
+        .unwrap();
+    assert_eq!(set.get_str("subject_id").unwrap(), "F001-2023");
+    assert!(statement
+        .get_document("u")
+        .and_then(|update| update.get_document("$unset"))
+        .unwrap()
+        .contains_key("removed_at"));
+
+    let statement = upsert_statement(
+        &course,
+        CourseStorage::Unified,
+        &semester,
+        CourseCategory::Basic,
+    )
+    .unwrap();
+    let update = statement.get_document("u").unwrap();
+    assert_eq!(
+        update.get_document("$set").unwrap().get_str("semester"),
+        Ok("2023-1")
+    );
+    assert_eq!(
+        update.get_document("$addToSet").unwrap(),
+        &doc! { "categories": "basic" }
+    );
+
+    assert_eq!(
+        parse_collection_name("course_2023-winter_전공과목"),
+        Some((Semester::new(2023, Term::Winter), CourseCategory::Major))
+    );
+    assert_eq!(parse_collection_name("course_changelog"), None);
+}
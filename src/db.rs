@@ -0,0 +1,56 @@
+use crate::calendar::AcademicEvent;
+use crate::changelog::CourseChange;
+use crate::course::{get_collection_name, Course, CourseCategory, Semester};
+use crate::meal::Meal;
+use crate::Notice;
+use anyhow::{Context, Result};
+use mongodb::{options::ClientOptions, Client, Collection};
+
+pub const DATABASE_NAME: &str = "ajou";
+
+/// Connects to the MongoDB given by the `MONGODB` env var.
+pub async fn connect() -> Result<Client> {
+    let connection_uri = std::env::var("MONGODB").context("MONGODB must be set")?;
+    let client_options = ClientOptions::parse(connection_uri).await?;
+    Ok(Client::with_options(client_options)?)
+}
+
+pub fn notice_collection(client: &Client) -> Collection<Notice> {
+    client
+        .database(DATABASE_NAME)
+        .collection::<Notice>("notice")
+}
+
+pub fn calendar_collection(client: &Client) -> Collection<AcademicEvent> {
+    client
+        .database(DATABASE_NAME)
+        .collection::<AcademicEvent>("calendar")
+}
+
+pub fn meal_collection(client: &Client) -> Collection<Meal> {
+    client.database(DATABASE_NAME).collection::<Meal>("meal")
+}
+
+/// The `course_<semester>_<category>` collection.
+pub fn course_collection(
+    client: &Client,
+    semester: &Semester,
+    category: CourseCategory,
+) -> Collection<Course> {
+    client
+        .database(DATABASE_NAME)
+        .collection::<Course>(&get_collection_name(semester, category))
+}
+
+/// Every semester and category, see [`crate::course::CourseStorage::Unified`].
+pub fn courses_collection(client: &Client) -> Collection<Course> {
+    client
+        .database(DATABASE_NAME)
+        .collection::<Course>("courses")
+}
+
+pub fn course_changelog_collection(client: &Client) -> Collection<CourseChange> {
+    client
+        .database(DATABASE_NAME)
+        .collection::<CourseChange>("course_changelog")
+}
@@ -0,0 +1,323 @@
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Weekday};
+use chrono_tz::Asia::Seoul;
+use chrono_tz::Tz;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadlineKind {
+    /// A date on its own, e.g. "8월 25일(금)"
+    Date,
+    /// "~8/25", "8/25까지", "마감: 8/25"
+    Until,
+    /// "8/21 ~ 8/25", "8월 21일부터 25일까지"
+    Range,
+}
+
+/// A date expression found in a notice, resolved to KST dates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deadline {
+    pub kind: DeadlineKind,
+    /// First day of a [`DeadlineKind::Range`]
+    pub start: Option<NaiveDate>,
+    pub end: NaiveDate,
+    /// Time of day of `end`, if given
+    pub time: Option<NaiveTime>,
+    /// The expression as written
+    pub text: String,
+}
+
+impl Deadline {
+    /// When it's over: `end` at `time`, or at the end of the day, KST.
+    pub fn due_at(&self) -> chrono::DateTime<Tz> {
+        let time = self
+            .time
+            .unwrap_or_else(|| NaiveTime::from_hms_opt(23, 59, 59).unwrap());
+        Seoul
+            .from_local_datetime(&self.end.and_time(time))
+            .earliest()
+            .unwrap_or_else(|| Seoul.from_utc_datetime(&self.end.and_time(time)))
+    }
+}
+
+/// `{p}y`, `{p}m`, `{p}d`/`{p}dd`, `{p}sep`, `{p}wd` and `{p}t` groups of a date.
+fn date_pattern(p: &str) -> String {
+    format!(
+        r"(?:(?P<{p}y>\d{{4}})\s*(?:[./\-]|년)\s*)?(?P<{p}m>\d{{1,2}})\s*(?:월\s*(?P<{p}d>\d{{1,2}})\s*일|(?P<{p}sep>[/.\-])\s*(?P<{p}dd>\d{{1,2}})\.?){}{}",
+        weekday_pattern(&format!("{p}wd")),
+        time_pattern(&format!("{p}t"))
+    )
+}
+
+fn weekday_pattern(name: &str) -> String {
+    format!(r"(?:\s*\(\s*(?P<{name}>[월화수목금토일])(?:요일)?\s*\))?")
+}
+
+fn time_pattern(name: &str) -> String {
+    format!(
+        r"(?:\s*(?P<{name}>(?:오전|오후)\s*\d{{1,2}}\s*시(?:\s*\d{{1,2}}\s*분|\s*반)?|\d{{1,2}}:\d{{2}}))?"
+    )
+}
+
+fn regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(&format!(
+            r"(?P<tilde>[~∼～]\s*)?{start}(?:\s*(?:[~∼～\-–]|부터)\s*(?:{end}|(?P<eday>\d{{1,2}})\s*일?{eday_wd}{eday_t}))?(?P<until>\s*까지)?",
+            start = date_pattern("s"),
+            end = date_pattern("e"),
+            eday_wd = weekday_pattern("edwd"),
+            eday_t = time_pattern("edt"),
+        ))
+        .unwrap()
+    })
+}
+
+/// Finds the date expressions of `text`, resolving missing years against `posted_on` (KST).
+///
+/// A date without a year is taken in the year that puts it no more than 90 days before the
+/// post date, unless a weekday annotation only fits another year.
+pub fn extract_deadlines(text: &str, posted_on: NaiveDate) -> Vec<Deadline> {
+    extract_dates(text, posted_on, 90)
+}
+
+/// Same as [`extract_deadlines`], a date without a year being taken no more than
+/// `days_before` days before `anchor`.
+pub fn extract_dates(text: &str, anchor: NaiveDate, days_before: i64) -> Vec<Deadline> {
+    find_dates(text, anchor, days_before, false)
+}
+
+/// Same as [`extract_dates`] for text that holds nothing but dates, e.g. a cell of the academic
+/// calendar, where a bare "03.02" is a date rather than a score or a version.
+pub fn extract_cell_dates(text: &str, anchor: NaiveDate, days_before: i64) -> Vec<Deadline> {
+    find_dates(text, anchor, days_before, true)
+}
+
+fn find_dates(text: &str, anchor: NaiveDate, days_before: i64, dates_only: bool) -> Vec<Deadline> {
+    let mut deadlines: Vec<Deadline> = vec![];
+
+    for caps in regex().captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        // Part of a bigger number, e.g. "2023/4" in "12023/4"
+        if text[..whole.start()]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_ascii_digit())
+        {
+            continue;
+        }
+        if let Some(deadline) = parse_match(text, &caps, anchor, days_before, dates_only) {
+            if !deadlines
+                .iter()
+                .any(|d| (d.start, d.end, d.time) == (deadline.start, deadline.end, deadline.time))
+            {
+                deadlines.push(deadline);
+            }
+        }
+    }
+
+    deadlines
+}
+
+fn parse_match(
+    text: &str,
+    caps: &Captures,
+    anchor: NaiveDate,
+    days_before: i64,
+    dates_only: bool,
+) -> Option<Deadline> {
+    let whole = caps.get(0)?;
+    let has_end = caps.name("em").is_some() || caps.name("eday").is_some();
+    let until = caps.name("tilde").is_some()
+        || caps.name("until").is_some()
+        || ["마감", "기한"]
+            .iter()
+            .any(|word| tail(&text[..whole.start()], 12).contains(word));
+
+    // "3.5" is more often a score than March 5th, and "1-2" a range of grades
+    let loose = |p: &str| {
+        caps.name(&format!("{p}sep"))
+            .is_some_and(|sep| sep.as_str() != "/")
+            && caps.name(&format!("{p}y")).is_none()
+            && caps.name(&format!("{p}wd")).is_none()
+    };
+    if loose("s") && !has_end && !until && !dates_only {
+        return None;
+    }
+
+    let start = resolve_date(caps, "s", anchor, days_before)?;
+    let start_time = caps.name("st").and_then(|t| parse_time(t.as_str()));
+
+    let (kind, start, end, time) = if caps.name("em").is_some() {
+        let end = resolve_date(caps, "e", start, 0)?;
+        let time = caps.name("et").and_then(|t| parse_time(t.as_str()));
+        (DeadlineKind::Range, Some(start), end, time)
+    } else if let Some(day) = caps.name("eday") {
+        let day: u32 = day.as_str().parse().ok()?;
+        let mut end = start.with_day(day)?;
+        if end < start {
+            end = NaiveDate::from_ymd_opt(
+                start.year() + start.month() as i32 / 12,
+                start.month() % 12 + 1,
+                day,
+            )?;
+        }
+        let weekday = caps.name("edwd").and_then(|wd| parse_weekday(wd.as_str()));
+        if weekday.is_some_and(|weekday| weekday != end.weekday()) {
+            return None;
+        }
+        let time = caps.name("edt").and_then(|t| parse_time(t.as_str()));
+        (DeadlineKind::Range, Some(start), end, time)
+    } else if until {
+        (DeadlineKind::Until, None, start, start_time)
+    } else {
+        (DeadlineKind::Date, None, start, start_time)
+    };
+
+    Some(Deadline {
+        kind,
+        start,
+        end,
+        time,
+        text: whole.as_str().trim().to_string(),
+    })
+}
+
+/// The last `n` characters of `s`.
+fn tail(s: &str, n: usize) -> &str {
+    let start = s
+        .char_indices()
+        .rev()
+        .nth(n.saturating_sub(1))
+        .map_or(0, |(i, _)| i);
+    &s[start..]
+}
+
+/// The date of the `{p}` groups, picking the year when it's missing (see [`extract_deadlines`]).
+fn resolve_date(
+    caps: &Captures,
+    p: &str,
+    anchor: NaiveDate,
+    days_before: i64,
+) -> Option<NaiveDate> {
+    let number = |name: &str| -> Option<u32> { caps.name(name)?.as_str().parse().ok() };
+
+    let month = number(&format!("{p}m"))?;
+    let day = number(&format!("{p}d")).or_else(|| number(&format!("{p}dd")))?;
+    let weekday = caps
+        .name(&format!("{p}wd"))
+        .and_then(|wd| parse_weekday(wd.as_str()));
+
+    if let Some(year) = number(&format!("{p}y")) {
+        return NaiveDate::from_ymd_opt(year as i32, month, day);
+    }
+
+    let candidates: Vec<NaiveDate> = (anchor.year() - 1..=anchor.year() + 1)
+        .filter_map(|year| NaiveDate::from_ymd_opt(year, month, day))
+        .collect();
+    let earliest = anchor - Duration::days(days_before);
+
+    let by_weekday: Vec<NaiveDate> = candidates
+        .iter()
+        .copied()
+        .filter(|date| weekday.is_some_and(|weekday| date.weekday() == weekday))
+        .collect();
+    let candidates = if by_weekday.is_empty() {
+        candidates
+    } else {
+        by_weekday
+    };
+
+    candidates
+        .iter()
+        .copied()
+        .find(|date| *date >= earliest)
+        .or_else(|| candidates.last().copied())
+}
+
+fn parse_weekday(weekday: &str) -> Option<Weekday> {
+    Some(match weekday {
+        "월" => Weekday::Mon,
+        "화" => Weekday::Tue,
+        "수" => Weekday::Wed,
+        "목" => Weekday::Thu,
+        "금" => Weekday::Fri,
+        "토" => Weekday::Sat,
+        "일" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+/// "18:00", "오후 6시", "오전 10시 30분", "오후 2시 반"
+fn parse_time(time: &str) -> Option<NaiveTime> {
+    if let Some((hour, minute)) = time.split_once(':') {
+        return NaiveTime::from_hms_opt(hour.trim().parse().ok()?, minute.trim().parse().ok()?, 0);
+    }
+
+    let am = time.starts_with("오전");
+    let pm = time.starts_with("오후");
+    let digits: Vec<u32> = time
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| s.parse().ok())
+        .collect();
+    let mut hour = *digits.first()?;
+    let minute = match digits.get(1) {
+        Some(minute) => *minute,
+        None if time.ends_with('반') => 30,
+        None => 0,
+    };
+    if pm && hour < 12 {
+        hour += 12;
+    }
+    // 오전 12시 is midnight
+    if am && hour == 12 {
+        hour = 0;
+    }
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+#[test]
+fn test_extract_deadlines() {
+    let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    let posted_on = date(2023, 8, 10);
+
+    let deadlines = extract_deadlines("2학기 수강신청 안내 (~8/25까지)", posted_on);
+    assert_eq!(deadlines.len(), 1);
+    assert_eq!(deadlines[0].kind, DeadlineKind::Until);
+    assert_eq!(deadlines[0].end, date(2023, 8, 25));
+    assert_eq!(deadlines[0].text, "~8/25까지");
+
+    let deadlines = extract_deadlines(
+        "신청기간: 8월 21일(월) 오전 10시 ~ 8월 25일(금) 오후 6시",
+        posted_on,
+    );
+    assert_eq!(deadlines[0].kind, DeadlineKind::Range);
+    assert_eq!(deadlines[0].start, Some(date(2023, 8, 21)));
+    assert_eq!(deadlines[0].end, date(2023, 8, 25));
+    assert_eq!(deadlines[0].time, NaiveTime::from_hms_opt(18, 0, 0));
+
+    assert_eq!(parse_time("오전 12시"), NaiveTime::from_hms_opt(0, 0, 0));
+    assert_eq!(
+        parse_time("오후 12시 반"),
+        NaiveTime::from_hms_opt(12, 30, 0)
+    );
+
+    let deadlines = extract_deadlines("접수 8.21.~25. / 마감: 2023-09-01 17:00", posted_on);
+    assert_eq!(deadlines.len(), 2);
+    assert_eq!(deadlines[0].end, date(2023, 8, 25));
+    assert_eq!(deadlines[1].kind, DeadlineKind::Until);
+    assert_eq!(deadlines[1].end, date(2023, 9, 1));
+    assert_eq!(deadlines[1].time, NaiveTime::from_hms_opt(17, 0, 0));
+
+    // Posted in December about January, and a weekday only 2024 fits
+    let deadlines = extract_deadlines("12/28 ~ 1/5, 1월 8일(월) 발표", date(2023, 12, 20));
+    assert_eq!(deadlines[0].start, Some(date(2023, 12, 28)));
+    assert_eq!(deadlines[0].end, date(2024, 1, 5));
+    assert_eq!(deadlines[1].end, date(2024, 1, 8));
+
+    // Scores and versions aren't dates
+    assert!(extract_deadlines("평점 3.5 이상, 제12023/4호", posted_on).is_empty());
+}
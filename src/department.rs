@@ -0,0 +1,235 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Writer of a notice as a stable organization, whatever it's called this year.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Department {
+    /// Canonical id stored on notices, never renamed
+    pub id: String,
+    /// Current name
+    pub name: String,
+    /// Former names and spellings seen as writers
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Id of the organization this one belongs to
+    #[serde(default)]
+    pub parent: Option<String>,
+}
+
+/// Departments by id, and the writer names resolving to them.
+#[derive(Debug)]
+pub struct Directory {
+    departments: Vec<Department>,
+    by_name: HashMap<String, usize>,
+}
+
+impl Directory {
+    /// Checks that ids and names are unique and that parents exist without cycles.
+    pub fn new(departments: Vec<Department>) -> Result<Self> {
+        let mut ids = HashMap::new();
+        for (i, department) in departments.iter().enumerate() {
+            if ids.insert(department.id.as_str(), i).is_some() {
+                return Err(anyhow!("Duplicate department id {}", department.id));
+            }
+        }
+
+        let mut by_name = HashMap::new();
+        for (i, department) in departments.iter().enumerate() {
+            for name in std::iter::once(&department.name).chain(&department.aliases) {
+                if let Some(other) = by_name.insert(name_key(name), i) {
+                    if other != i {
+                        return Err(anyhow!(
+                            "{name} is used by both {} and {}",
+                            departments[other].id,
+                            department.id
+                        ));
+                    }
+                }
+            }
+
+            // Walking up from each department must end at a root
+            let mut parent = department.parent.as_deref();
+            let mut depth = 0;
+            while let Some(id) = parent {
+                let index = *ids.get(id).ok_or_else(|| {
+                    anyhow!("Unknown parent {id} of department {}", department.id)
+                })?;
+                depth += 1;
+                if depth > departments.len() {
+                    return Err(anyhow!("Parent cycle through department {}", department.id));
+                }
+                parent = departments[index].parent.as_deref();
+            }
+        }
+
+        Ok(Directory {
+            departments,
+            by_name,
+        })
+    }
+
+    /// Loaded from the JSON list at `DEPARTMENTS` on first use, the built-in directory otherwise.
+    pub fn shared() -> Result<&'static Directory> {
+        static SHARED: OnceLock<Directory> = OnceLock::new();
+
+        if let Some(directory) = SHARED.get() {
+            return Ok(directory);
+        }
+        let departments = match std::env::var("DEPARTMENTS") {
+            Ok(path) => {
+                let data =
+                    std::fs::read(&path).with_context(|| format!("Reading departments {path}"))?;
+                serde_json::from_slice(&data)
+                    .with_context(|| format!("Parsing departments {path}"))?
+            }
+            Err(_) => default_departments(),
+        };
+        let directory = Directory::new(departments)?;
+        Ok(SHARED.get_or_init(|| directory))
+    }
+
+    pub fn departments(&self) -> &[Department] {
+        &self.departments
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Department> {
+        self.departments
+            .iter()
+            .find(|department| department.id == id)
+    }
+
+    /// The department a notice writer (name or alias, spacing ignored) stands for.
+    pub fn resolve(&self, writer: &str) -> Option<&Department> {
+        self.by_name
+            .get(&name_key(writer))
+            .map(|&i| &self.departments[i])
+    }
+
+    /// Ids of `id` and of every organization below it, empty if `id` is unknown.
+    pub fn subtree(&self, id: &str) -> Vec<&str> {
+        let mut ids = match self.get(id) {
+            Some(department) => vec![department.id.as_str()],
+            None => return vec![],
+        };
+
+        let mut i = 0;
+        while i < ids.len() {
+            let current = ids[i];
+            ids.extend(
+                self.departments
+                    .iter()
+                    .filter(|department| department.parent.as_deref() == Some(current))
+                    .map(|department| department.id.as_str()),
+            );
+            i += 1;
+        }
+
+        ids
+    }
+
+    /// Names from `id` up to its root, e.g. ["학사팀", "교무처", "아주대학교"].
+    pub fn path(&self, id: &str) -> Vec<&str> {
+        let mut path = vec![];
+        let mut current = self.get(id);
+        while let Some(department) = current {
+            path.push(department.name.as_str());
+            current = department.parent.as_deref().and_then(|id| self.get(id));
+        }
+        path
+    }
+}
+
+/// "학 사 팀" and "학사팀" are the same writer.
+fn name_key(name: &str) -> String {
+    name.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// The offices posting most often on the main notice board.
+pub fn default_departments() -> Vec<Department> {
+    let department = |id: &str, name: &str, aliases: &[&str], parent: Option<&str>| Department {
+        id: id.to_string(),
+        name: name.to_string(),
+        aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+        parent: parent.map(String::from),
+    };
+
+    vec![
+        department("ajou", "아주대학교", &["대학본부"], None),
+        department("academic-affairs", "교무처", &[], Some("ajou")),
+        department(
+            "academic",
+            "학사팀",
+            &["학사지원팀", "교무팀(학사)"],
+            Some("academic-affairs"),
+        ),
+        department("faculty", "교무팀", &[], Some("academic-affairs")),
+        department("student-affairs", "학생처", &[], Some("ajou")),
+        department(
+            "scholarship",
+            "장학팀",
+            &["장학복지팀"],
+            Some("student-affairs"),
+        ),
+        department(
+            "student-support",
+            "학생지원팀",
+            &["학생팀"],
+            Some("student-affairs"),
+        ),
+        department("counseling", "학생상담센터", &[], Some("student-affairs")),
+        department("admissions", "입학처", &["입학사정관실"], Some("ajou")),
+        department("admissions-team", "입학팀", &[], Some("admissions")),
+        department("international", "국제처", &["국제교류처"], Some("ajou")),
+        department(
+            "international-team",
+            "국제교류팀",
+            &["국제팀"],
+            Some("international"),
+        ),
+        department(
+            "career",
+            "대학일자리플러스센터",
+            &["대학일자리센터", "취업지원팀"],
+            Some("ajou"),
+        ),
+        department("library", "중앙도서관", &["도서관"], Some("ajou")),
+        department(
+            "it",
+            "정보통신팀",
+            &["IT지원팀", "정보전산팀"],
+            Some("ajou"),
+        ),
+        department("dormitory", "생활관", &["기숙사"], Some("ajou")),
+    ]
+}
+
+#[test]
+fn test_directory() {
+    let directory = Directory::new(default_departments()).unwrap();
+
+    assert_eq!(directory.resolve("학사팀").unwrap().id, "academic");
+    assert_eq!(directory.resolve("학사지원팀").unwrap().id, "academic");
+    assert_eq!(directory.resolve(" 장학 팀 ").unwrap().id, "scholarship");
+    assert!(directory.resolve("알 수 없음").is_none());
+
+    assert_eq!(
+        directory.subtree("student-affairs"),
+        vec![
+            "student-affairs",
+            "scholarship",
+            "student-support",
+            "counseling"
+        ]
+    );
+    assert!(directory.subtree("nowhere").is_empty());
+    assert_eq!(
+        directory.path("academic"),
+        vec!["학사팀", "교무처", "아주대학교"]
+    );
+
+    let mut cyclic = default_departments();
+    cyclic[0].parent = Some("academic".to_string());
+    assert!(Directory::new(cyclic).is_err());
+}
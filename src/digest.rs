@@ -0,0 +1,438 @@
+use crate::export::NoticeQuery;
+use crate::Notice;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate};
+use chrono_tz::Tz;
+use clap::ValueEnum;
+use futures::stream::TryStreamExt;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use mongodb::bson::doc;
+use mongodb::Collection;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestPeriod {
+    /// One KST day
+    Daily,
+    /// Monday to Sunday, KST
+    Weekly,
+}
+
+impl DigestPeriod {
+    /// The window (inclusive) ending on `to`.
+    pub fn window(self, to: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            DigestPeriod::Daily => (to, to),
+            DigestPeriod::Weekly => (to - Duration::days(6), to),
+        }
+    }
+
+    /// The last complete window before `seoul_now`: yesterday, or last Monday to Sunday.
+    pub fn last_window(self, seoul_now: DateTime<Tz>) -> (NaiveDate, NaiveDate) {
+        let today = seoul_now.date_naive();
+        let to = match self {
+            DigestPeriod::Daily => today - Duration::days(1),
+            DigestPeriod::Weekly => {
+                today - Duration::days(today.weekday().num_days_from_monday() as i64 + 1)
+            }
+        };
+        self.window(to)
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            DigestPeriod::Daily => "일간",
+            DigestPeriod::Weekly => "주간",
+        }
+    }
+}
+
+impl FromStr for DigestPeriod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "daily" => Ok(DigestPeriod::Daily),
+            "weekly" => Ok(DigestPeriod::Weekly),
+            other => Err(anyhow!("Unknown digest period {other} (daily|weekly)")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Markup {
+    Text,
+    Markdown,
+    Html,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WriterGroup {
+    pub writer: String,
+    pub notices: Vec<Notice>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryGroup {
+    pub category: String,
+    pub writers: Vec<WriterGroup>,
+}
+
+/// Notices posted over a window, grouped by category then writer (both sorted by name).
+#[derive(Debug, Clone, Serialize)]
+pub struct Digest {
+    pub period: DigestPeriod,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub categories: Vec<CategoryGroup>,
+}
+
+impl Digest {
+    /// Groups `notices`, oldest first within each writer.
+    pub fn new(period: DigestPeriod, from: NaiveDate, to: NaiveDate, notices: Vec<Notice>) -> Self {
+        let mut groups: BTreeMap<String, BTreeMap<String, Vec<Notice>>> = BTreeMap::new();
+        for notice in notices {
+            groups
+                .entry(notice.category.clone())
+                .or_default()
+                .entry(notice.writer.clone())
+                .or_default()
+                .push(notice);
+        }
+
+        let categories = groups
+            .into_iter()
+            .map(|(category, writers)| CategoryGroup {
+                category,
+                writers: writers
+                    .into_iter()
+                    .map(|(writer, mut notices)| {
+                        notices.sort_by_key(|notice| notice.id);
+                        WriterGroup { writer, notices }
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Digest {
+            period,
+            from,
+            to,
+            categories,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.notices().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.categories.is_empty()
+    }
+
+    pub fn notices(&self) -> impl Iterator<Item = &Notice> {
+        self.categories
+            .iter()
+            .flat_map(|category| &category.writers)
+            .flat_map(|writer| &writer.notices)
+    }
+
+    /// e.g. "2023-04-10" or "2023-04-03 ~ 2023-04-09"
+    fn range(&self) -> String {
+        if self.from == self.to {
+            self.from.to_string()
+        } else {
+            format!("{} ~ {}", self.from, self.to)
+        }
+    }
+
+    pub fn subject(&self) -> String {
+        format!(
+            "[아주대 공지] {} 요약 {} ({}건)",
+            self.period.label(),
+            self.range(),
+            self.len()
+        )
+    }
+
+    pub fn render(&self, markup: Markup) -> String {
+        match markup {
+            Markup::Text => self.to_text(),
+            Markup::Markdown => self.to_markdown(),
+            Markup::Html => self.to_html(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = format!("{}\n", self.subject());
+        for category in &self.categories {
+            let _ = write!(out, "\n[{}]\n", category.category);
+            for writer in &category.writers {
+                let _ = writeln!(out, "  {}", writer.writer);
+                for notice in &writer.notices {
+                    let _ = writeln!(out, "    - {} ({})", notice.title, notice.date);
+                    let _ = writeln!(out, "      {}", notice.link);
+                }
+            }
+        }
+        out
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "# 아주대학교 공지 {} 요약\n\n{} · {}건\n",
+            self.period.label(),
+            self.range(),
+            self.len()
+        );
+        for category in &self.categories {
+            let _ = write!(out, "\n## {}\n", escape_markdown(&category.category));
+            for writer in &category.writers {
+                let _ = write!(out, "\n### {}\n\n", escape_markdown(&writer.writer));
+                for notice in &writer.notices {
+                    let _ = writeln!(
+                        out,
+                        "- [{}](<{}>) ({})",
+                        escape_markdown(&notice.title),
+                        notice.link,
+                        notice.date
+                    );
+                }
+            }
+        }
+        out
+    }
+
+    /// A self-contained HTML email body.
+    pub fn to_html(&self) -> String {
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html lang=\"ko\">\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>\n\
+             <h1>아주대학교 공지 {} 요약</h1>\n<p>{} · {}건</p>\n",
+            escape_html(&self.subject()),
+            self.period.label(),
+            self.range(),
+            self.len()
+        );
+        for category in &self.categories {
+            let _ = writeln!(out, "<h2>{}</h2>", escape_html(&category.category));
+            for writer in &category.writers {
+                let _ = writeln!(out, "<h3>{}</h3>\n<ul>", escape_html(&writer.writer));
+                for notice in &writer.notices {
+                    let _ = writeln!(
+                        out,
+                        "<li><a href=\"{}\">{}</a> ({})</li>",
+                        escape_html(&notice.link),
+                        escape_html(&notice.title),
+                        escape_html(&notice.date)
+                    );
+                }
+                out.push_str("</ul>\n");
+            }
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn escape_markdown(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '#' | '<' | '>') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Builds the digest of the stored notices posted from `from` to `to` (inclusive, KST).
+pub async fn collect_digest(
+    notice_collection: &Collection<Notice>,
+    period: DigestPeriod,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Digest> {
+    let query = NoticeQuery {
+        from: Some(from),
+        to: Some(to),
+        ..Default::default()
+    };
+    // Queried by date, checked again per notice as `date` is stored as shown on the board
+    let notices: Vec<Notice> = query
+        .find(notice_collection, query.filter())
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(Digest::new(period, from, to, notices))
+}
+
+/// SMTP settings for sending digests.
+#[derive(Debug, Clone)]
+pub struct MailConfig {
+    pub host: String,
+    /// 465 means implicit TLS, anything else STARTTLS (587 by default)
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: Mailbox,
+    pub to: Vec<Mailbox>,
+}
+
+impl MailConfig {
+    /// Reads `SMTP_HOST`, `SMTP_PORT`, `SMTP_USER`, `SMTP_PASSWORD`, `DIGEST_FROM` and `DIGEST_TO`
+    /// (comma separated), `None` without `SMTP_HOST`.
+    pub fn from_env() -> Result<Option<Self>> {
+        let env = |key| {
+            std::env::var(key)
+                .ok()
+                .filter(|v: &String| !v.trim().is_empty())
+        };
+
+        let Some(host) = env("SMTP_HOST") else {
+            return Ok(None);
+        };
+        let port = env("SMTP_PORT")
+            .map(|port| port.trim().parse::<u16>())
+            .transpose()
+            .map_err(|e| anyhow!("Invalid SMTP_PORT: {e}"))?;
+        let from = env("DIGEST_FROM")
+            .ok_or_else(|| anyhow!("DIGEST_FROM is required with SMTP_HOST"))?
+            .parse::<Mailbox>()?;
+        let to = env("DIGEST_TO")
+            .ok_or_else(|| anyhow!("DIGEST_TO is required with SMTP_HOST"))?
+            .split(',')
+            .map(str::trim)
+            .filter(|address| !address.is_empty())
+            .map(|address| address.parse::<Mailbox>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Some(MailConfig {
+            host,
+            port,
+            username: env("SMTP_USER"),
+            password: env("SMTP_PASSWORD"),
+            from,
+            to,
+        }))
+    }
+
+    /// Sends `digest` as a plain text + HTML email to every recipient.
+    pub async fn send(&self, digest: &Digest) -> Result<()> {
+        self.send_mail(&digest.subject(), digest.to_text(), digest.to_html())
+            .await
+    }
+
+    /// Sends a plain text + HTML email to every recipient.
+    pub async fn send_mail(&self, subject: &str, text: String, html: String) -> Result<()> {
+        let mut builder = Message::builder().from(self.from.clone()).subject(subject);
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        let message = builder.multipart(MultiPart::alternative_plain_html(text, html))?;
+
+        let mut transport = match self.port {
+            Some(465) => AsyncSmtpTransport::<Tokio1Executor>::relay(&self.host)?.port(465),
+            Some(port) => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)?.port(port)
+            }
+            None => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)?,
+        };
+        if let Some(username) = &self.username {
+            transport = transport.credentials(Credentials::new(
+                username.clone(),
+                self.password.clone().unwrap_or_default(),
+            ));
+        }
+
+        transport.build().send(message).await?;
+        Ok(())
+    }
+}
+
+/// `DIGEST_SCHEDULE`: comma separated periods the updater sends, `daily` by default.
+pub fn scheduled_periods() -> Result<Vec<DigestPeriod>> {
+    match std::env::var("DIGEST_SCHEDULE") {
+        Ok(periods) => periods
+            .split(',')
+            .filter(|period| !period.trim().is_empty())
+            .map(str::parse)
+            .collect(),
+        Err(_) => Ok(vec![DigestPeriod::Daily]),
+    }
+}
+
+#[test]
+fn test_digest() {
+    use chrono::TimeZone;
+    use chrono_tz::Asia::Seoul;
+
+    // Wednesday
+    let now = Seoul.with_ymd_and_hms(2023, 4, 12, 8, 0, 0).unwrap();
+    let date = |d| NaiveDate::from_ymd_opt(2023, 4, d).unwrap();
+    assert_eq!(DigestPeriod::Daily.last_window(now), (date(11), date(11)));
+    assert_eq!(DigestPeriod::Weekly.last_window(now), (date(3), date(9)));
+
+    let notice = |id, category: &str, writer: &str, title: &str| Notice {
+        id,
+        category: category.to_string(),
+        title: title.to_string(),
+        date: "23.04.11".to_string(),
+        link: format!("https://www.ajou.ac.kr/kr/ajou/notice.do?articleNo={id}"),
+        writer: writer.to_string(),
+        ..Default::default()
+    };
+    let digest = Digest::new(
+        DigestPeriod::Daily,
+        date(11),
+        date(11),
+        vec![
+            notice(3, "장학", "장학팀", "국가장학금 <2차> 신청"),
+            notice(2, "학사", "학사팀", "수강신청 [정정] 안내"),
+            notice(1, "장학", "장학팀", "교내장학 안내"),
+        ],
+    );
+
+    assert_eq!(digest.len(), 3);
+    assert_eq!(digest.categories[0].category, "장학");
+    assert_eq!(digest.categories[0].writers[0].notices[0].id, 1);
+    assert!(digest
+        .to_text()
+        .starts_with("[아주대 공지] 일간 요약 2023-04-11 (3건)\n"));
+    assert!(digest
+        .to_markdown()
+        .contains("- [수강신청 \\[정정\\] 안내](<https://www.ajou.ac.kr/kr/ajou/notice.do?articleNo=2>) (23.04.11)"));
+    assert!(digest.to_html().contains("국가장학금 &lt;2차&gt; 신청"));
+
+    let query = NoticeQuery {
+        from: Some(date(3)),
+        to: Some(date(9)),
+        ..Default::default()
+    };
+    let dates = query.filter().get_document("date").unwrap().clone();
+    let dates = dates.get_array("$in").unwrap();
+    assert_eq!(dates.len(), 7 * 4);
+    assert!(dates.contains(&"23.04.03".into()));
+    assert!(dates.contains(&"2023-04-09".into()));
+}
@@ -0,0 +1,522 @@
+use crate::course::{CourseCategory, CourseStorage, Semester};
+use crate::Notice;
+use anyhow::{anyhow, Result};
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use chrono::NaiveDate;
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt, TryStreamExt};
+use mongodb::bson::{doc, Document};
+use mongodb::options::FindOptions;
+use mongodb::{Client, Collection};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::io::Write;
+use std::sync::Arc;
+
+/// Rows buffered per Parquet row group.
+const PARQUET_BATCH_SIZE: usize = 8192;
+
+/// UTF-8 BOM, so Excel opens Korean CSVs without mojibake.
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+    Parquet,
+}
+
+/// Writes serializable items one by one as CSV, NDJSON or Parquet.
+///
+/// Columns are the selected `fields` in order, or the fields of the first item.
+/// Missing fields are written as empty/null, nested values as JSON strings.
+pub struct ExportWriter<W: Write + Send> {
+    fields: Option<Vec<String>>,
+    sink: Sink<W>,
+    written: usize,
+}
+
+enum Sink<W: Write + Send> {
+    Csv(csv::Writer<W>),
+    Ndjson(W),
+    Parquet {
+        writer: Option<W>,
+        arrow: Option<ArrowWriter<W>>,
+        schema: Option<Arc<Schema>>,
+        rows: Vec<Map<String, Value>>,
+    },
+}
+
+impl<W: Write + Send> ExportWriter<W> {
+    pub fn new(mut writer: W, format: ExportFormat, fields: Option<Vec<String>>) -> Result<Self> {
+        let sink = match format {
+            ExportFormat::Csv => {
+                writer.write_all(UTF8_BOM)?;
+                Sink::Csv(csv::Writer::from_writer(writer))
+            }
+            ExportFormat::Ndjson => Sink::Ndjson(writer),
+            ExportFormat::Parquet => Sink::Parquet {
+                writer: Some(writer),
+                arrow: None,
+                schema: None,
+                rows: Vec::new(),
+            },
+        };
+
+        Ok(ExportWriter {
+            fields,
+            sink,
+            written: 0,
+        })
+    }
+
+    pub fn write<T: Serialize>(&mut self, item: &T) -> Result<()> {
+        let row = match serde_json::to_value(item)? {
+            Value::Object(map) => map,
+            other => return Err(anyhow!("Can only export objects, got {other}")),
+        };
+
+        let fields = self
+            .fields
+            .get_or_insert_with(|| row.keys().cloned().collect());
+        let row: Map<String, Value> = fields
+            .iter()
+            .map(|field| {
+                (
+                    field.clone(),
+                    row.get(field).cloned().unwrap_or(Value::Null),
+                )
+            })
+            .collect();
+
+        match &mut self.sink {
+            Sink::Csv(writer) => {
+                if self.written == 0 {
+                    writer.write_record(fields.iter())?;
+                }
+                writer.write_record(row.values().map(csv_cell))?;
+            }
+            Sink::Ndjson(writer) => {
+                serde_json::to_writer(&mut *writer, &row)?;
+                writer.write_all(b"\n")?;
+            }
+            Sink::Parquet { rows, .. } => {
+                rows.push(row);
+                if rows.len() >= PARQUET_BATCH_SIZE {
+                    self.flush_parquet()?;
+                }
+            }
+        }
+
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Number of items written so far.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// Flushes buffered rows and the Parquet footer, returning the number of items written.
+    pub fn finish(mut self) -> Result<usize> {
+        self.flush_parquet()?;
+
+        match &mut self.sink {
+            Sink::Csv(writer) => writer.flush()?,
+            Sink::Ndjson(writer) => writer.flush()?,
+            Sink::Parquet {
+                writer,
+                arrow,
+                schema,
+                ..
+            } => match arrow.take() {
+                Some(arrow) => {
+                    arrow.close()?;
+                }
+                // Nothing was written, still leave a valid (empty) file behind
+                None => {
+                    let schema = schema.clone().unwrap_or_else(|| {
+                        Arc::new(infer_schema(
+                            self.fields.as_deref().unwrap_or_default(),
+                            &[],
+                        ))
+                    });
+                    let writer = writer.take().expect("Parquet writer already taken");
+                    ArrowWriter::try_new(writer, schema, Some(parquet_props()))?.close()?;
+                }
+            },
+        }
+
+        Ok(self.written)
+    }
+
+    fn flush_parquet(&mut self) -> Result<()> {
+        let Sink::Parquet {
+            writer,
+            arrow,
+            schema,
+            rows,
+        } = &mut self.sink
+        else {
+            return Ok(());
+        };
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let fields = self.fields.as_deref().unwrap_or_default();
+        // Column types are inferred from the first batch and kept for the rest of the file
+        let schema = schema
+            .get_or_insert_with(|| Arc::new(infer_schema(fields, rows)))
+            .clone();
+
+        if arrow.is_none() {
+            let writer = writer.take().expect("Parquet writer already taken");
+            *arrow = Some(ArrowWriter::try_new(
+                writer,
+                schema.clone(),
+                Some(parquet_props()),
+            )?);
+        }
+
+        let columns: Vec<ArrayRef> = schema
+            .fields()
+            .iter()
+            .map(|field| column(field.name(), field.data_type(), rows))
+            .collect::<Result<_>>()?;
+        let batch = RecordBatch::try_new(schema, columns)?;
+        arrow.as_mut().unwrap().write(&batch)?;
+        rows.clear();
+
+        Ok(())
+    }
+}
+
+/// Writes every item of `stream` (e.g. a MongoDB cursor) to `writer`.
+pub async fn write_stream<T, W, S, E>(writer: &mut ExportWriter<W>, stream: S) -> Result<()>
+where
+    T: Serialize,
+    W: Write + Send,
+    S: Stream<Item = Result<T, E>>,
+    E: Into<anyhow::Error>,
+{
+    futures::pin_mut!(stream);
+    while let Some(item) = stream.try_next().await.map_err(Into::into)? {
+        writer.write(&item)?;
+    }
+    Ok(())
+}
+
+/// Longest date range [`NoticeQuery::filter`] puts in the query.
+pub const MAX_QUERIED_DAYS: i64 = 31;
+
+/// Which stored notices to export.
+#[derive(Debug, Default, Clone)]
+pub struct NoticeQuery {
+    pub category: Option<String>,
+    /// Posted on or after, KST
+    pub from: Option<NaiveDate>,
+    /// Posted on or before, KST
+    pub to: Option<NaiveDate>,
+    /// Department ids, usually a [`crate::department::Directory::subtree`], any if empty
+    pub departments: Vec<String>,
+    /// Board list URL the notice was posted on
+    pub board: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl NoticeQuery {
+    /// The MongoDB part of the query. Ranges of up to [`MAX_QUERIED_DAYS`] days are matched on
+    /// the ways `date` may be written, longer ones are left to [`NoticeQuery::matches`].
+    pub fn filter(&self) -> Document {
+        let mut filter = doc! {};
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            let days = (to - from).num_days();
+            if (0..MAX_QUERIED_DAYS).contains(&days) {
+                let dates: Vec<String> = from
+                    .iter_days()
+                    .take_while(|day| *day <= to)
+                    .flat_map(|day| {
+                        ["%y.%m.%d", "%y-%m-%d", "%Y.%m.%d", "%Y-%m-%d"]
+                            .map(|format| day.format(format).to_string())
+                    })
+                    .collect();
+                filter.insert("date", doc! { "$in": dates });
+            }
+        }
+        if let Some(category) = &self.category {
+            filter.insert("category", category);
+        }
+        if !self.departments.is_empty() {
+            filter.insert("department", doc! { "$in": &self.departments });
+        }
+        if let Some(board) = &self.board {
+            // Links are the board URL followed by the article query
+            filter.insert(
+                "link",
+                doc! { "$regex": format!("^{}", regex::escape(board)) },
+            );
+        }
+        filter
+    }
+
+    /// Whether [`NoticeQuery::filter`] leaves nothing for [`NoticeQuery::matches`] to drop, so
+    /// the limit can be applied by MongoDB.
+    fn fully_filtered(&self) -> bool {
+        match (self.from, self.to) {
+            (None, None) => true,
+            (Some(from), Some(to)) => (0..MAX_QUERIED_DAYS).contains(&(to - from).num_days()),
+            _ => false,
+        }
+    }
+
+    /// The stored notices matching `filter` (usually [`NoticeQuery::filter`]) and the query,
+    /// newest first, at most `limit` of them.
+    pub async fn find<'a>(
+        &'a self,
+        notice_collection: &Collection<Notice>,
+        filter: Document,
+    ) -> Result<BoxStream<'a, Result<Notice>>> {
+        let fully_filtered = self.fully_filtered();
+        let options = FindOptions::builder()
+            .sort(doc! { "id": -1 })
+            .limit(self.limit.filter(|_| fully_filtered))
+            .build();
+        // Otherwise counted once the notices outside the date range are dropped
+        let limit = self.limit.map_or(usize::MAX, |limit| limit.max(0) as usize);
+
+        Ok(notice_collection
+            .find(filter, options)
+            .await?
+            .map_err(anyhow::Error::from)
+            .try_filter(|notice| futures::future::ready(self.matches(notice)))
+            .take(limit)
+            .boxed())
+    }
+
+    pub fn matches(&self, notice: &Notice) -> bool {
+        if self
+            .category
+            .as_ref()
+            .is_some_and(|c| c != &notice.category)
+        {
+            return false;
+        }
+        if !self.departments.is_empty()
+            && !notice
+                .department
+                .as_ref()
+                .is_some_and(|department| self.departments.contains(department))
+        {
+            return false;
+        }
+        if self
+            .board
+            .as_ref()
+            .is_some_and(|board| !notice.link.starts_with(board.as_str()))
+        {
+            return false;
+        }
+        if self.from.is_none() && self.to.is_none() {
+            return true;
+        }
+
+        match notice.posted_on() {
+            Some(date) => {
+                self.from.is_none_or(|from| date >= from) && self.to.is_none_or(|to| date <= to)
+            }
+            None => false,
+        }
+    }
+}
+
+/// Streams the `notice` collection, newest first, into `writer`.
+///
+/// The date range is checked per notice since `date` is stored as shown on the board.
+pub async fn export_notices<W: Write + Send>(
+    notice_collection: &Collection<Notice>,
+    query: &NoticeQuery,
+    mut writer: ExportWriter<W>,
+) -> Result<usize> {
+    let notices = query.find(notice_collection, query.filter()).await?;
+    write_stream(&mut writer, notices).await?;
+
+    writer.finish()
+}
+
+/// Streams the stored courses of the given categories into `writer`.
+pub async fn export_courses<W: Write + Send>(
+    client: &Client,
+    storage: CourseStorage,
+    semester: &Semester,
+    categories: &[CourseCategory],
+    mut writer: ExportWriter<W>,
+) -> Result<usize> {
+    for (collection, filter) in storage.sources(client, semester, categories)? {
+        let cursor = collection.find(filter, None).await?;
+        write_stream(&mut writer, cursor).await?;
+    }
+
+    writer.finish()
+}
+
+fn parquet_props() -> WriterProperties {
+    WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build()
+}
+
+fn csv_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn infer_schema(fields: &[String], rows: &[Map<String, Value>]) -> Schema {
+    let fields: Vec<Field> = fields
+        .iter()
+        .map(|name| {
+            let values: Vec<&Value> = rows
+                .iter()
+                .filter_map(|row| row.get(name))
+                .filter(|v| !v.is_null())
+                .collect();
+
+            let data_type = if values.is_empty() {
+                DataType::Utf8
+            } else if values.iter().all(|v| v.is_boolean()) {
+                DataType::Boolean
+            } else if values.iter().all(|v| v.is_i64()) {
+                DataType::Int64
+            } else if values.iter().all(|v| v.is_number()) {
+                DataType::Float64
+            } else {
+                DataType::Utf8
+            };
+            Field::new(name, data_type, true)
+        })
+        .collect();
+
+    Schema::new(fields)
+}
+
+/// Fails on a value that doesn't fit the type inferred from the first batch, rather than
+/// writing it as null.
+fn column(name: &str, data_type: &DataType, rows: &[Map<String, Value>]) -> Result<ArrayRef> {
+    let values = rows.iter().map(|row| row.get(name).unwrap_or(&Value::Null));
+
+    let fits = |value: &Value| match data_type {
+        DataType::Boolean => value.is_boolean(),
+        DataType::Int64 => value.is_i64(),
+        DataType::Float64 => value.is_number(),
+        _ => true,
+    };
+    if let Some(value) = values
+        .clone()
+        .find(|value| !value.is_null() && !fits(value))
+    {
+        return Err(anyhow!(
+            "Column {name} is {data_type} in Parquet from its first {PARQUET_BATCH_SIZE} rows, \
+             a later row has {value} (export as csv or ndjson instead)"
+        ));
+    }
+
+    Ok(match data_type {
+        DataType::Boolean => Arc::new(values.map(Value::as_bool).collect::<BooleanArray>()),
+        DataType::Int64 => Arc::new(values.map(Value::as_i64).collect::<Int64Array>()),
+        DataType::Float64 => Arc::new(values.map(Value::as_f64).collect::<Float64Array>()),
+        _ => Arc::new(
+            values
+                .map(|v| (!v.is_null()).then(|| csv_cell(v)))
+                .collect::<StringArray>(),
+        ),
+    })
+}
+
+#[test]
+fn test_export_csv() {
+    #[derive(Serialize)]
+    struct Row {
+        id: i32,
+        title: &'static str,
+        tags: Vec<&'static str>,
+    }
+
+    let mut writer = ExportWriter::new(
+        Vec::new(),
+        ExportFormat::Csv,
+        Some(vec!["title".into(), "id".into(), "tags".into()]),
+    )
+    .unwrap();
+    writer
+        .write(&Row {
+            id: 1,
+            title: "수강신청, 안내",
+            tags: vec!["학사"],
+        })
+        .unwrap();
+
+    let Sink::Csv(csv) = writer.sink else {
+        unreachable!()
+    };
+    let out = String::from_utf8(csv.into_inner().unwrap()).unwrap();
+    assert_eq!(
+        out,
+        "\u{feff}title,id,tags\n\"수강신청, 안내\",1,\"[\"\"학사\"\"]\"\n"
+    );
+}
+
+#[test]
+fn test_export_parquet_mixed_types() {
+    let mut writer = ExportWriter::new(
+        Vec::new(),
+        ExportFormat::Parquet,
+        Some(vec!["id".into(), "credits".into()]),
+    )
+    .unwrap();
+    for id in 0..PARQUET_BATCH_SIZE {
+        writer
+            .write(&serde_json::json!({ "id": id, "credits": 3 }))
+            .unwrap();
+    }
+
+    // Fits the Int64 columns of the first batch
+    writer
+        .write(&serde_json::json!({ "id": 1, "credits": null }))
+        .unwrap();
+    writer
+        .write(&serde_json::json!({ "id": 2, "credits": 1.5 }))
+        .unwrap();
+    let error = writer.finish().unwrap_err().to_string();
+    assert!(error.contains("credits"), "{error}");
+}
+
+#[test]
+fn test_notice_query_limit() {
+    let date = |d| NaiveDate::from_ymd_opt(2023, 4, d).unwrap();
+    let query = |from, to| NoticeQuery {
+        from,
+        to,
+        limit: Some(10),
+        ..Default::default()
+    };
+
+    assert!(query(None, None).fully_filtered());
+    assert!(query(Some(date(1)), Some(date(30))).fully_filtered());
+    // Left to `matches`, so the limit can't be left to MongoDB
+    assert!(!query(Some(date(1)), None).fully_filtered());
+    assert!(!query(None, Some(date(30))).fully_filtered());
+    assert!(!query(Some(date(30)), Some(date(1))).fully_filtered());
+    let long = NoticeQuery {
+        to: NaiveDate::from_ymd_opt(2023, 6, 1),
+        ..query(Some(date(1)), None)
+    };
+    assert!(!long.fully_filtered());
+    assert!(long.filter().get("date").is_none());
+}
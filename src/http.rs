@@ -0,0 +1,506 @@
+use crate::network::NetworkConfig;
+use crate::politeness::{RateLimiter, Robots};
+use crate::tls::TlsConfig;
+use crate::MY_USER_AGENT;
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use reqwest::header::{
+    HeaderMap, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::time::Duration;
+
+/// Settings of the client shared by every scraper.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub connect_timeout: Duration,
+    /// Where raw responses are kept between runs, in memory only if `None`.
+    pub cache_dir: Option<PathBuf>,
+    /// Cached responses younger than this are returned without any request.
+    pub cache_ttl: Duration,
+    /// Responses kept by the in-memory cache, the least recently used ones being dropped.
+    pub cache_capacity: usize,
+    pub tls: TlsConfig,
+    pub network: NetworkConfig,
+    /// Average requests per second to a single host.
+    pub rate_limit: f64,
+    /// Requests to a single host that may go out back to back.
+    pub burst: u32,
+    /// Requests in flight at once, over all hosts.
+    pub max_concurrency: usize,
+    /// Fetch each host's robots.txt and refuse disallowed paths, also honours `Crawl-delay`.
+    pub respect_robots: bool,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            connect_timeout: Duration::from_secs(10),
+            cache_dir: None,
+            cache_ttl: Duration::ZERO,
+            cache_capacity: 256,
+            tls: TlsConfig::default(),
+            network: NetworkConfig::default(),
+            rate_limit: 1.0,
+            burst: 3,
+            max_concurrency: 4,
+            respect_robots: false,
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Reads `HTTP_CACHE_DIR`, `HTTP_CACHE_TTL` (seconds), `HTTP_CACHE_CAPACITY`,
+    /// `HTTP_RATE_LIMIT` (requests per second),
+    /// `HTTP_BURST`, `HTTP_MAX_CONCURRENCY`, `HTTP_RESPECT_ROBOTS`, the `TLS_*` and `SCRAPER_*` settings.
+    pub fn from_env() -> Result<Self> {
+        let mut config = ClientConfig {
+            tls: TlsConfig::from_env(),
+            network: NetworkConfig::from_env()?,
+            ..Default::default()
+        };
+
+        if let Ok(dir) = std::env::var("HTTP_CACHE_DIR") {
+            config.cache_dir = Some(PathBuf::from(dir));
+        }
+        if let Some(ttl) = env_parse::<u64>("HTTP_CACHE_TTL") {
+            config.cache_ttl = Duration::from_secs(ttl);
+        }
+        if let Some(capacity) = env_parse("HTTP_CACHE_CAPACITY") {
+            config.cache_capacity = capacity;
+        }
+        if let Some(rate_limit) = env_parse("HTTP_RATE_LIMIT") {
+            config.rate_limit = rate_limit;
+        }
+        if let Some(burst) = env_parse("HTTP_BURST") {
+            config.burst = burst;
+        }
+        if let Some(max_concurrency) = env_parse("HTTP_MAX_CONCURRENCY") {
+            config.max_concurrency = max_concurrency;
+        }
+        if let Some(respect_robots) = env_parse("HTTP_RESPECT_ROBOTS") {
+            config.respect_robots = respect_robots;
+        }
+
+        Ok(config)
+    }
+}
+
+pub(crate) fn env_parse<T: std::str::FromStr>(key: &str) -> Option<T> {
+    std::env::var(key).ok().and_then(|v| v.trim().parse().ok())
+}
+
+/// Where a response body came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    /// Downloaded in full.
+    Fetched,
+    /// The server answered 304 to our `If-None-Match`/`If-Modified-Since`.
+    NotModified,
+    /// Younger than the cache TTL, no request was sent.
+    Cached,
+}
+
+#[derive(Debug, Clone)]
+pub struct Fetched {
+    pub body: String,
+    pub freshness: Freshness,
+}
+
+impl Fetched {
+    /// Whether the body is the same one we got last time.
+    pub fn is_unchanged(&self) -> bool {
+        self.freshness != Freshness::Fetched
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// Unix seconds
+    stored_at: i64,
+    body: String,
+}
+
+impl CacheEntry {
+    fn age(&self) -> Duration {
+        Duration::from_secs((Utc::now().timestamp() - self.stored_at).max(0) as u64)
+    }
+}
+
+/// Entries by key, with when each was last used.
+#[derive(Default)]
+struct MemoryCache {
+    entries: HashMap<String, (CacheEntry, u64)>,
+    clock: u64,
+    capacity: usize,
+}
+
+impl MemoryCache {
+    fn new(capacity: usize) -> Self {
+        MemoryCache {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<CacheEntry> {
+        self.clock += 1;
+        let (entry, used) = self.entries.get_mut(key)?;
+        *used = self.clock;
+        Some(entry.clone())
+    }
+
+    fn put(&mut self, key: &str, entry: CacheEntry) {
+        self.clock += 1;
+        self.entries.insert(key.to_string(), (entry, self.clock));
+
+        // A linear scan is fine for the few hundred entries kept
+        while self.entries.len() > self.capacity {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+enum CacheStore {
+    Memory(Mutex<MemoryCache>),
+    Disk(PathBuf),
+}
+
+impl CacheStore {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        match self {
+            CacheStore::Memory(cache) => cache.lock().unwrap().get(key),
+            CacheStore::Disk(dir) => {
+                let data = std::fs::read(dir.join(format!("{key}.json"))).ok()?;
+                serde_json::from_slice(&data).ok()
+            }
+        }
+    }
+
+    fn put(&self, key: &str, entry: CacheEntry) {
+        match self {
+            CacheStore::Memory(cache) => cache.lock().unwrap().put(key, entry),
+            CacheStore::Disk(dir) => {
+                // The cache is best effort, a failed write only costs a request next time
+                let write = std::fs::create_dir_all(dir).and_then(|_| {
+                    std::fs::write(
+                        dir.join(format!("{key}.json")),
+                        serde_json::to_vec(&entry).unwrap_or_default(),
+                    )
+                });
+                if let Err(e) = write {
+                    println!("Failed to write HTTP cache entry for {}: {e}", entry.url);
+                }
+            }
+        }
+    }
+}
+
+fn cache_key(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// `reqwest::Client` with conditional requests, an optional on-disk response cache and
+/// politeness towards Ajou's servers (per-host rate limit, concurrency cap, robots.txt).
+///
+/// Every scraper should go through [`HttpClient::shared`].
+pub struct HttpClient {
+    client: reqwest::Client,
+    cache: CacheStore,
+    cache_ttl: Duration,
+    limiter: RateLimiter,
+    in_flight: Semaphore,
+    respect_robots: bool,
+    robots: tokio::sync::Mutex<HashMap<String, Arc<Robots>>>,
+}
+
+impl HttpClient {
+    pub fn new(config: ClientConfig) -> Result<Self> {
+        let builder = reqwest::Client::builder()
+            .use_preconfigured_tls(config.tls.client_config()?)
+            .connect_timeout(config.connect_timeout)
+            // header 없이 보내면 404
+            .user_agent(MY_USER_AGENT);
+        let client = config.network.apply(builder)?.build()?;
+
+        let cache = match config.cache_dir {
+            Some(dir) => CacheStore::Disk(dir),
+            None => CacheStore::Memory(Mutex::new(MemoryCache::new(config.cache_capacity))),
+        };
+
+        Ok(HttpClient {
+            client,
+            cache,
+            cache_ttl: config.cache_ttl,
+            limiter: RateLimiter::new(config.rate_limit, config.burst),
+            in_flight: Semaphore::new(config.max_concurrency.max(1)),
+            respect_robots: config.respect_robots,
+            robots: tokio::sync::Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The process-wide client, configured from the environment on first use.
+    pub fn shared() -> Result<&'static HttpClient> {
+        static SHARED: OnceLock<HttpClient> = OnceLock::new();
+
+        if let Some(client) = SHARED.get() {
+            return Ok(client);
+        }
+        let client = HttpClient::new(ClientConfig::from_env()?)?;
+        Ok(SHARED.get_or_init(|| client))
+    }
+
+    pub fn inner(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// Waits for our turn to hit `url`, the returned permit must be held until the body is read.
+    async fn permit(&self, url: &str) -> Result<SemaphorePermit<'_>> {
+        let url = Url::parse(url)?;
+        let host = url.host_str().unwrap_or_default().to_string();
+
+        if self.respect_robots {
+            let robots = self.robots_for(&url).await;
+            let path = match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_string(),
+            };
+            if !robots.is_allowed(&path) {
+                return Err(anyhow!("robots.txt of {host} disallows {path}"));
+            }
+        }
+
+        self.limiter.acquire(&host).await;
+        Ok(self.in_flight.acquire().await?)
+    }
+
+    async fn robots_for(&self, url: &Url) -> Arc<Robots> {
+        let origin = url.origin().ascii_serialization();
+        // Held while fetching, so concurrent first requests don't all download it
+        let mut robots = self.robots.lock().await;
+        if let Some(rules) = robots.get(&origin) {
+            return rules.clone();
+        }
+
+        let host = url.host_str().unwrap_or_default();
+        self.limiter.acquire(host).await;
+        let rules = match self.client.get(format!("{origin}/robots.txt")).send().await {
+            Ok(res) if res.status().is_success() => {
+                Robots::parse(&res.text().await.unwrap_or_default())
+            }
+            // Missing or unreachable robots.txt means no restrictions
+            _ => Robots::default(),
+        };
+        if let Some(delay) = rules.crawl_delay {
+            self.limiter.set_crawl_delay(host, delay);
+        }
+
+        let rules = Arc::new(rules);
+        robots.insert(origin, rules.clone());
+        rules
+    }
+
+    /// GETs `url`, revalidating the last response with `If-None-Match`/`If-Modified-Since`.
+    pub async fn get_text(&self, url: &str) -> Result<Fetched> {
+        let key = cache_key(&[b"GET", url.as_bytes()]);
+        let cached = self.cache.get(&key);
+
+        if let Some(entry) = &cached {
+            if entry.age() < self.cache_ttl {
+                return Ok(Fetched {
+                    body: entry.body.clone(),
+                    freshness: Freshness::Cached,
+                });
+            }
+        }
+
+        let mut request = self.client.get(url);
+        if let Some(entry) = &cached {
+            if let Some(etag) = &entry.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let _permit = self.permit(url).await?;
+        let res = request.send().await?;
+
+        if res.status() == StatusCode::NOT_MODIFIED {
+            if let Some(mut entry) = cached {
+                entry.stored_at = Utc::now().timestamp();
+                let body = entry.body.clone();
+                self.cache.put(&key, entry);
+
+                return Ok(Fetched {
+                    body,
+                    freshness: Freshness::NotModified,
+                });
+            }
+        }
+
+        let res = res.error_for_status()?;
+        let header = |name| {
+            res.headers()
+                .get(name)
+                .and_then(|v: &HeaderValue| v.to_str().ok())
+                .map(String::from)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        let body = res.text().await?;
+
+        self.cache.put(
+            &key,
+            CacheEntry {
+                url: url.to_string(),
+                etag,
+                last_modified,
+                stored_at: Utc::now().timestamp(),
+                body: body.clone(),
+            },
+        );
+
+        Ok(Fetched {
+            body,
+            freshness: Freshness::Fetched,
+        })
+    }
+
+    /// GETs `url` without reading or writing the response cache, for pages with personal data.
+    pub async fn get_uncached(&self, url: &str) -> Result<String> {
+        let _permit = self.permit(url).await?;
+        Ok(self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?)
+    }
+
+    /// POSTs `payload` as JSON. Responses for which `cacheable` holds are reused for the cache TTL.
+    pub async fn post_json(
+        &self,
+        url: &str,
+        headers: HeaderMap,
+        payload: &serde_json::Value,
+        timeout: Duration,
+        cacheable: impl Fn(&str) -> bool,
+    ) -> Result<Fetched> {
+        let key = cache_key(&[b"POST", url.as_bytes(), payload.to_string().as_bytes()]);
+
+        if !self.cache_ttl.is_zero() {
+            if let Some(entry) = self.cache.get(&key) {
+                if entry.age() < self.cache_ttl {
+                    return Ok(Fetched {
+                        body: entry.body,
+                        freshness: Freshness::Cached,
+                    });
+                }
+            }
+        }
+
+        let _permit = self.permit(url).await?;
+        let body = self
+            .client
+            .post(url)
+            .headers(headers)
+            .timeout(timeout)
+            .json(payload)
+            .send()
+            .await?
+            .text()
+            .await?;
+
+        if !self.cache_ttl.is_zero() && cacheable(&body) {
+            self.cache.put(
+                &key,
+                CacheEntry {
+                    url: url.to_string(),
+                    etag: None,
+                    last_modified: None,
+                    stored_at: Utc::now().timestamp(),
+                    body: body.clone(),
+                },
+            );
+        }
+
+        Ok(Fetched {
+            body,
+            freshness: Freshness::Fetched,
+        })
+    }
+}
+
+#[test]
+fn test_disk_cache() {
+    let dir = std::env::temp_dir().join(format!("ajou-http-cache-{}", std::process::id()));
+    let store = CacheStore::Disk(dir.clone());
+    let key = cache_key(&[b"GET", crate::AJOU_LINK.as_bytes()]);
+
+    assert!(store.get(&key).is_none());
+    store.put(
+        &key,
+        CacheEntry {
+            url: crate::AJOU_LINK.to_string(),
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+            stored_at: Utc::now().timestamp(),
+            body: "<html>공지</html>".to_string(),
+        },
+    );
+
+    let entry = store.get(&key).unwrap();
+    assert_eq!(entry.etag.as_deref(), Some("\"abc\""));
+    assert_eq!(entry.body, "<html>공지</html>");
+    assert!(entry.age() < Duration::from_secs(5));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_memory_cache_eviction() {
+    let entry = |body: &str| CacheEntry {
+        url: crate::AJOU_LINK.to_string(),
+        etag: None,
+        last_modified: None,
+        stored_at: Utc::now().timestamp(),
+        body: body.to_string(),
+    };
+    let mut cache = MemoryCache::new(2);
+    cache.put("a", entry("a"));
+    cache.put("b", entry("b"));
+    // "a" is now more recently used than "b"
+    assert!(cache.get("a").is_some());
+    cache.put("c", entry("c"));
+
+    assert_eq!(cache.entries.len(), 2);
+    assert!(cache.get("b").is_none());
+    assert_eq!(cache.get("a").unwrap().body, "a");
+    assert_eq!(cache.get("c").unwrap().body, "c");
+}
@@ -0,0 +1,400 @@
+use crate::calendar::{self, AcademicEvent, EventCategory};
+use crate::deadline::{Deadline, DeadlineKind};
+use crate::export::NoticeQuery;
+use crate::Notice;
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use futures::stream::TryStreamExt;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use mongodb::bson::doc;
+use mongodb::Collection;
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::Path;
+
+const PRODID: &str = "-//Ajou-Parser//Notice deadlines//KO";
+
+/// Content lines are folded at 75 octets (RFC 5545 3.1).
+const MAX_LINE: usize = 75;
+
+/// Builds an iCalendar feed with one VEVENT per notice deadline.
+pub struct Calendar {
+    name: String,
+    /// Reminders before each event ends, e.g. one day before a deadline
+    alarms: Vec<Duration>,
+    lines: Vec<String>,
+}
+
+impl Calendar {
+    pub fn new(name: &str) -> Self {
+        Calendar {
+            name: name.to_string(),
+            alarms: vec![Duration::days(1)],
+            lines: vec![],
+        }
+    }
+
+    pub fn alarms(mut self, alarms: Vec<Duration>) -> Self {
+        self.alarms = alarms;
+        self
+    }
+
+    /// Adds the deadlines of `notice`, `now` being the `DTSTAMP` of the events.
+    pub fn add_notice(&mut self, notice: &Notice, now: DateTime<Utc>) {
+        for deadline in &notice.deadlines {
+            self.add_event(notice, deadline, now);
+        }
+    }
+
+    fn add_event(&mut self, notice: &Notice, deadline: &Deadline, now: DateTime<Utc>) {
+        let summary = match deadline.kind {
+            DeadlineKind::Until => format!("[{}] 마감: {}", notice.category, notice.title),
+            _ => format!("[{}] {}", notice.category, notice.title),
+        };
+
+        let mut event = vec![
+            "BEGIN:VEVENT".to_string(),
+            // Same notice, same UIDs: clients update the events instead of adding new ones
+            format!("UID:{}@ajou.ac.kr", deadline_uid(notice, deadline)),
+            format!("DTSTAMP:{}", format_utc(now)),
+        ];
+
+        match deadline.time {
+            // Timed deadlines are a point in time, given an end for the alarms relative to it
+            Some(_) => {
+                let due_at = format_utc(deadline.due_at().with_timezone(&Utc));
+                event.push(format!("DTSTART:{due_at}"));
+                event.push(format!("DTEND:{due_at}"));
+            }
+            None => {
+                let start = deadline.start.unwrap_or(deadline.end);
+                event.push(format!("DTSTART;VALUE=DATE:{}", format_date(start)));
+                event.push(format!(
+                    "DTEND;VALUE=DATE:{}",
+                    format_date(deadline.end + Duration::days(1))
+                ));
+            }
+        }
+
+        event.push(format!("SUMMARY:{}", escape_text(&summary)));
+        event.push(format!(
+            "DESCRIPTION:{}",
+            escape_text(&format!(
+                "{}\n{}\n{}",
+                deadline.text, notice.writer, notice.link
+            ))
+        ));
+        event.push(format!("URL:{}", notice.link));
+        if !notice.category.is_empty() {
+            event.push(format!("CATEGORIES:{}", escape_text(&notice.category)));
+        }
+
+        for alarm in &self.alarms {
+            event.extend([
+                "BEGIN:VALARM".to_string(),
+                "ACTION:DISPLAY".to_string(),
+                format!("DESCRIPTION:{}", escape_text(&summary)),
+                format!("TRIGGER;RELATED=END:-{}", format_duration(*alarm)),
+                "END:VALARM".to_string(),
+            ]);
+        }
+        event.push("END:VEVENT".to_string());
+
+        self.lines.extend(event);
+    }
+
+    /// Adds an all-day event, its UID derived from the title and start date.
+    pub fn add_academic_event(&mut self, event: &AcademicEvent, now: DateTime<Utc>) {
+        let key = format!("{}|{}", event.start, event.title);
+        let hash = format!("{:x}", Sha256::digest(key.as_bytes()));
+
+        self.lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:academic-{}@ajou.ac.kr", &hash[..16]),
+            format!("DTSTAMP:{}", format_utc(now)),
+            format!("DTSTART;VALUE=DATE:{}", format_date(event.start)),
+            format!(
+                "DTEND;VALUE=DATE:{}",
+                format_date(event.end + Duration::days(1))
+            ),
+            format!("SUMMARY:{}", escape_text(&event.title)),
+            format!("CATEGORIES:{}", category_name(event.category)),
+            // Breaks don't block the day in free/busy views
+            format!(
+                "TRANSP:{}",
+                if event.category.is_break() {
+                    "TRANSPARENT"
+                } else {
+                    "OPAQUE"
+                }
+            ),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+
+    /// The whole feed, CRLF separated and folded.
+    pub fn finish(self) -> String {
+        let header = [
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            format!("PRODID:{PRODID}"),
+            "CALSCALE:GREGORIAN".to_string(),
+            "METHOD:PUBLISH".to_string(),
+            format!("X-WR-CALNAME:{}", escape_text(&self.name)),
+            "X-WR-TIMEZONE:Asia/Seoul".to_string(),
+        ];
+
+        header
+            .into_iter()
+            .chain(self.lines)
+            .chain(["END:VCALENDAR".to_string()])
+            .map(|line| fold(&line))
+            .collect()
+    }
+}
+
+/// From the board, the notice and the deadline's dates, not its position among the notice's
+/// deadlines, which changes when they're extracted again.
+fn deadline_uid(notice: &Notice, deadline: &Deadline) -> String {
+    let board = notice.link.split('?').next().unwrap_or_default();
+    let key = format!(
+        "{board}|{}|{:?}|{}|{:?}",
+        notice.id, deadline.start, deadline.end, deadline.time
+    );
+    let hash = format!("{:x}", Sha256::digest(key.as_bytes()));
+    format!("notice-{}-{}", notice.id, &hash[..16])
+}
+
+fn category_name(category: EventCategory) -> &'static str {
+    match category {
+        EventCategory::Registration => "수강신청/등록",
+        EventCategory::Exam => "시험",
+        EventCategory::Semester => "학기",
+        EventCategory::Break => "방학",
+        EventCategory::Holiday => "휴일",
+        EventCategory::Other => "기타",
+    }
+}
+
+fn format_utc(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn format_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+/// e.g. "P1D", "PT3H", "PT30M"
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    if minutes % (24 * 60) == 0 {
+        format!("P{}D", minutes / (24 * 60))
+    } else if minutes % 60 == 0 {
+        format!("PT{}H", minutes / 60)
+    } else {
+        format!("PT{minutes}M")
+    }
+}
+
+fn escape_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Folds a content line at [`MAX_LINE`] octets without splitting characters, with its CRLF.
+fn fold(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() + 8);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE {
+            out.push_str("\r\n ");
+            // The leading space counts
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+/// The feed of the stored notices matching `query` that have deadlines.
+pub async fn notices_ics(
+    notice_collection: &Collection<Notice>,
+    query: &NoticeQuery,
+) -> Result<String> {
+    let mut filter = query.filter();
+    filter.insert("deadlines.0", doc! { "$exists": true });
+    let notices: Vec<Notice> = query
+        .find(notice_collection, filter)
+        .await?
+        .try_collect()
+        .await?;
+
+    let name = match &query.category {
+        Some(category) => format!("아주대 공지 ({category})"),
+        None => "아주대 공지".to_string(),
+    };
+    let mut calendar = Calendar::new(&name);
+    let now = Utc::now();
+    for notice in &notices {
+        calendar.add_notice(notice, now);
+    }
+
+    Ok(calendar.finish())
+}
+
+/// The feed of the stored academic calendar events overlapping `from..=to`.
+pub async fn academic_ics(
+    calendar_collection: &Collection<AcademicEvent>,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<String> {
+    let mut calendar = Calendar::new("아주대 학사일정");
+    let now = Utc::now();
+    for event in calendar::find_events(calendar_collection, from, to).await? {
+        calendar.add_academic_event(&event, now);
+    }
+    Ok(calendar.finish())
+}
+
+/// Writes the feed to `path` through a temporary file, so readers never see half of it.
+pub async fn write_ics(
+    notice_collection: &Collection<Notice>,
+    query: &NoticeQuery,
+    path: &Path,
+) -> Result<()> {
+    let ics = notices_ics(notice_collection, query).await?;
+    write_atomic(path, ics).await
+}
+
+pub async fn write_atomic(path: &Path, ics: String) -> Result<()> {
+    let tmp = path.with_extension("ics.tmp");
+    tokio::fs::write(&tmp, ics).await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+/// Serves `GET /notices.ics?category=..&board=..` at `addr`.
+pub async fn serve_ics(notice_collection: Collection<Notice>, addr: SocketAddr) -> Result<()> {
+    let make_service = make_service_fn(move |_| {
+        let notice_collection = notice_collection.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let notice_collection = notice_collection.clone();
+                async move { Ok::<_, Infallible>(handle_request(&notice_collection, request).await) }
+            }))
+        }
+    });
+
+    println!("Serving the notice calendar at http://{addr}/notices.ics");
+    Server::try_bind(&addr)?.serve(make_service).await?;
+    Ok(())
+}
+
+async fn handle_request(
+    notice_collection: &Collection<Notice>,
+    request: Request<Body>,
+) -> Response<Body> {
+    let response = |status: StatusCode, body: String| {
+        Response::builder()
+            .status(status)
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    if request.method() != Method::GET || request.uri().path() != "/notices.ics" {
+        return response(StatusCode::NOT_FOUND, "Not found\n".to_string());
+    }
+
+    let query = match Url::parse(&format!("http://localhost{}", request.uri())) {
+        Ok(url) => {
+            let param = |key: &str| {
+                url.query_pairs()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| v.into_owned())
+            };
+            NoticeQuery {
+                category: param("category"),
+                board: param("board"),
+                ..Default::default()
+            }
+        }
+        Err(e) => return response(StatusCode::BAD_REQUEST, format!("{e}\n")),
+    };
+
+    match notices_ics(notice_collection, &query).await {
+        Ok(ics) => Response::builder()
+            .header("Content-Type", "text/calendar; charset=utf-8")
+            .header("Content-Disposition", "inline; filename=\"notices.ics\"")
+            .body(Body::from(ics))
+            .unwrap(),
+        Err(e) => {
+            println!("Failed to build the calendar: {e}");
+            response(StatusCode::INTERNAL_SERVER_ERROR, format!("{e}\n"))
+        }
+    }
+}
+
+#[test]
+fn test_calendar() {
+    use chrono::TimeZone;
+
+    let notice = Notice {
+        id: 12345,
+        category: "학사".to_string(),
+        title: "2학기 수강신청 안내; 필독, 전체".to_string(),
+        date: "23.08.10".to_string(),
+        link: "https://www.ajou.ac.kr/kr/ajou/notice.do?mode=view&articleNo=12345".to_string(),
+        writer: "학사팀".to_string(),
+        deadlines: crate::deadline::extract_deadlines(
+            "~8/25(금) 18:00까지, 8/21 ~ 8/25",
+            NaiveDate::from_ymd_opt(2023, 8, 10).unwrap(),
+        ),
+        ..Default::default()
+    };
+    let now = Utc.with_ymd_and_hms(2023, 8, 10, 0, 0, 0).unwrap();
+    let mut calendar = Calendar::new("아주대 공지");
+    calendar.add_notice(&notice, now);
+    let ics = calendar.finish();
+
+    assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+    assert!(ics.ends_with("END:VCALENDAR\r\n"));
+    let uids = |ics: &str| {
+        let mut uids: Vec<String> = ics
+            .lines()
+            .filter(|line| line.starts_with("UID:notice-12345-"))
+            .map(String::from)
+            .collect();
+        uids.sort();
+        uids
+    };
+    assert_eq!(uids(&ics).len(), 2);
+    assert_ne!(uids(&ics)[0], uids(&ics)[1]);
+    // Extracted again in another order, the events keep their UIDs
+    let mut reordered = notice.clone();
+    reordered.deadlines.reverse();
+    let mut calendar = Calendar::new("아주대 공지");
+    calendar.add_notice(&reordered, now);
+    assert_eq!(uids(&calendar.finish()), uids(&ics));
+    // 18:00 KST
+    assert!(ics.contains("DTSTART:20230825T090000Z\r\nDTEND:20230825T090000Z\r\n"));
+    assert!(ics.contains("DTSTART;VALUE=DATE:20230821\r\nDTEND;VALUE=DATE:20230826\r\n"));
+    assert!(ics.contains("SUMMARY:[학사] 마감: 2학기 수강신청 안내\\; 필독\\, 전체\r\n"));
+    assert!(ics.contains("TRIGGER;RELATED=END:-P1D\r\n"));
+    assert!(ics.lines().all(|line| line.len() <= MAX_LINE));
+}
@@ -3,8 +3,34 @@ extern crate chrono_tz;
 extern crate serde_derive;
 extern crate serde_json;
 
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 
+pub mod browser;
+pub mod building;
+pub mod calendar;
+pub mod changelog;
+pub mod cli;
+pub mod course;
+pub mod db;
+pub mod deadline;
+pub mod department;
+pub mod digest;
+pub mod export;
+pub mod http;
+pub mod ics;
+pub mod meal;
+pub mod network;
+pub mod normalize;
+pub mod notice;
+pub mod occupancy;
+pub mod people;
+pub mod politeness;
+pub mod scheduler;
+pub mod session;
+pub mod timetable;
+pub mod tls;
+
 pub const AJOU_LINK: &str = "https://www.ajou.ac.kr/kr/ajou/notice.do";
 pub const MY_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/112.0.0.0 Safari/537.36";
 
@@ -12,8 +38,47 @@ pub const MY_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) Apple
 pub struct Notice {
     pub id: i32,
     pub category: String,
+    /// Normalized by the board's [`normalize::TitlePipeline`]
     pub title: String,
+    /// As posted, kept for auditing the normalization
+    #[serde(default)]
+    pub raw_title: String,
     pub date: String,
     pub link: String,
     pub writer: String,
+    /// Canonical [`department::Department`] id of `writer`, `None` if it isn't in the directory
+    #[serde(default)]
+    pub department: Option<String>,
+    /// Dates found in the title and body, see [`deadline::extract_deadlines`]
+    #[serde(default)]
+    pub deadlines: Vec<deadline::Deadline>,
+}
+
+impl Notice {
+    /// Post date of the notice, `date` is kept as shown on the board.
+    pub fn posted_on(&self) -> Option<NaiveDate> {
+        parse_board_date(&self.date)
+    }
+}
+
+/// Parses board dates such as "23.04.10", "2023.04.10" or "2023-04-10".
+pub fn parse_board_date(date: &str) -> Option<NaiveDate> {
+    let date = date.trim();
+    // %Y would happily read "23" as the year 23
+    let formats = match date.len() {
+        8 => ["%y.%m.%d", "%y-%m-%d"],
+        _ => ["%Y.%m.%d", "%Y-%m-%d"],
+    };
+    formats
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(date, format).ok())
+}
+
+#[test]
+fn test_parse_board_date() {
+    let expected = NaiveDate::from_ymd_opt(2023, 4, 10);
+    assert_eq!(parse_board_date("23.04.10"), expected);
+    assert_eq!(parse_board_date("2023.04.10"), expected);
+    assert_eq!(parse_board_date(" 2023-04-10 "), expected);
+    assert_eq!(parse_board_date("알 수 없음"), None);
 }
@@ -0,0 +1,279 @@
+use crate::http::HttpClient;
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use chrono_tz::Asia::Seoul;
+use clap::ValueEnum;
+use futures::stream::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::options::{FindOptions, UpdateOptions};
+use mongodb::Collection;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+
+/// Menu page of the campus restaurants, one restaurant and day per request.
+pub const MEAL_LINK: &str = "https://www.ajou.ac.kr/kr/life/food.do";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Restaurant {
+    /// 기숙사식당
+    Dormitory,
+    /// 교직원식당
+    Staff,
+}
+
+impl Restaurant {
+    pub const ALL: [Restaurant; 2] = [Restaurant::Dormitory, Restaurant::Staff];
+
+    /// `restaurantSeq` of the menu page
+    fn seq(self) -> u32 {
+        match self {
+            Restaurant::Dormitory => 63,
+            Restaurant::Staff => 220,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Restaurant::Dormitory => "기숙사식당",
+            Restaurant::Staff => "교직원식당",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MealTime {
+    Breakfast,
+    Lunch,
+    Dinner,
+}
+
+impl MealTime {
+    /// "조식", "중식(11:30~13:30)", "저녁" ...
+    fn from_header(header: &str) -> Option<Self> {
+        let has = |words: &[&str]| words.iter().any(|word| header.contains(word));
+        if has(&["조식", "아침"]) {
+            Some(MealTime::Breakfast)
+        } else if has(&["중식", "점심"]) {
+            Some(MealTime::Lunch)
+        } else if has(&["석식", "저녁"]) {
+            Some(MealTime::Dinner)
+        } else {
+            None
+        }
+    }
+}
+
+/// What one corner of a restaurant serves at a meal time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Meal {
+    pub restaurant: Restaurant,
+    pub date: NaiveDate,
+    pub time: MealTime,
+    /// e.g. "한식", "일품", `None` if the restaurant has a single line
+    pub corner: Option<String>,
+    pub menu: Vec<String>,
+    /// Won
+    pub price: Option<u32>,
+}
+
+/// Fetches the menu of `restaurant` on `date`.
+pub async fn meal_parse(restaurant: Restaurant, date: NaiveDate) -> Result<Vec<Meal>> {
+    let url = format!(
+        "{MEAL_LINK}?mode=list&restaurantSeq={}&srDt={}",
+        restaurant.seq(),
+        date
+    );
+    let body = HttpClient::shared()?.get_text(&url).await?.body;
+    Ok(parse_meals(&body, restaurant, date))
+}
+
+/// Fetches the menus of `restaurant` from Monday to Sunday of the week of `date`.
+pub async fn meal_parse_week(restaurant: Restaurant, date: NaiveDate) -> Result<Vec<Meal>> {
+    let monday = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+    let mut meals = vec![];
+    for day in 0..7 {
+        meals.append(&mut meal_parse(restaurant, monday + Duration::days(day)).await?);
+    }
+    Ok(meals)
+}
+
+/// Reads the rows of the menu table: a meal time header, then lines of menu.
+///
+/// In a cell, a `[코너]` or `<코너>` line starts a corner and a "4,500원" line is its price.
+pub fn parse_meals(body: &str, restaurant: Restaurant, date: NaiveDate) -> Vec<Meal> {
+    let document = Html::parse_document(body);
+    let rows = Selector::parse("tr").unwrap();
+    let headers = Selector::parse("th").unwrap();
+    let cells = Selector::parse("td").unwrap();
+
+    let mut meals = vec![];
+    for row in document.select(&rows) {
+        let Some(time) = row
+            .select(&headers)
+            .next()
+            .and_then(|header| MealTime::from_header(&header.text().collect::<String>()))
+        else {
+            continue;
+        };
+
+        for cell in row.select(&cells) {
+            let mut meal = Meal {
+                restaurant,
+                date,
+                time,
+                corner: None,
+                menu: vec![],
+                price: None,
+            };
+
+            for line in lines(cell) {
+                if let Some(corner) = parse_corner(&line) {
+                    if !meal.menu.is_empty() {
+                        meals.push(meal.clone());
+                    }
+                    meal.corner = Some(corner);
+                    meal.menu.clear();
+                    meal.price = None;
+                } else if let Some(price) = parse_price(&line) {
+                    meal.price = Some(price);
+                } else {
+                    meal.menu.push(line);
+                }
+            }
+
+            if !meal.menu.is_empty() {
+                meals.push(meal);
+            }
+        }
+    }
+
+    meals
+}
+
+/// Text of `cell` line by line, `<br>`s ending a line too, without "no service" notes.
+fn lines(cell: ElementRef) -> Vec<String> {
+    cell.text()
+        .flat_map(|text| text.lines())
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.contains("운영없음") && !line.contains("미운영"))
+        .map(String::from)
+        .collect()
+}
+
+fn parse_corner(line: &str) -> Option<String> {
+    let corner = line
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .or_else(|| {
+            line.strip_prefix('<')
+                .and_then(|rest| rest.strip_suffix('>'))
+        })?;
+    Some(corner.trim().to_string())
+}
+
+/// "4,500원", "₩4500"
+fn parse_price(line: &str) -> Option<u32> {
+    if !(line.ends_with('원') || line.starts_with('₩')) {
+        return None;
+    }
+    line.chars()
+        .filter(char::is_ascii_digit)
+        .collect::<String>()
+        .parse()
+        .ok()
+}
+
+/// Upserts `meals` by (restaurant, date, time, corner).
+pub async fn save_meals(meal_collection: &Collection<Meal>, meals: &[Meal]) -> Result<()> {
+    let options = UpdateOptions::builder().upsert(true).build();
+
+    for meal in meals {
+        let document = mongodb::bson::to_document(meal)?;
+        let key = |field: &str| {
+            document
+                .get(field)
+                .cloned()
+                .ok_or_else(|| anyhow!("Meal without {field}"))
+        };
+        let filter = doc! {
+            "restaurant": key("restaurant")?,
+            "date": key("date")?,
+            "time": key("time")?,
+            "corner": key("corner")?,
+        };
+        meal_collection
+            .update_one(filter, doc! { "$set": &document }, options.clone())
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Stored meals of `date`, every restaurant if `restaurant` is `None`.
+pub async fn find_meals(
+    meal_collection: &Collection<Meal>,
+    date: NaiveDate,
+    restaurant: Option<Restaurant>,
+) -> Result<Vec<Meal>> {
+    let mut filter = doc! { "date": date.to_string() };
+    if let Some(restaurant) = restaurant {
+        filter.insert("restaurant", mongodb::bson::to_bson(&restaurant)?);
+    }
+    let options = FindOptions::builder()
+        .sort(doc! { "restaurant": 1, "corner": 1 })
+        .build();
+
+    let mut meals: Vec<Meal> = meal_collection
+        .find(filter, options)
+        .await?
+        .try_collect()
+        .await?;
+    meals.sort_by_key(|meal| (meal.restaurant.name(), meal.time));
+    Ok(meals)
+}
+
+/// Today's (KST) stored menu.
+pub async fn todays_meals(
+    meal_collection: &Collection<Meal>,
+    restaurant: Option<Restaurant>,
+) -> Result<Vec<Meal>> {
+    let today = Utc::now().with_timezone(&Seoul).date_naive();
+    find_meals(meal_collection, today, restaurant).await
+}
+
+#[test]
+fn test_parse_meals() {
+    let body = r#"
+        <table>
+            <tr><th>구분</th><th>메뉴</th></tr>
+            <tr><th>조식</th><td>운영없음</td></tr>
+            <tr>
+                <th>중식 (11:30~13:30)</th>
+                <td>[한식]<br>김치찌개<br>계란말이<br>5,000원<br>[일품]<br>돈까스<br>6,000원</td>
+            </tr>
+            <tr><th>석식</th><td>제육볶음<br>미역국</td></tr>
+        </table>
+    "#;
+    let date = NaiveDate::from_ymd_opt(2023, 4, 10).unwrap();
+    let meals = parse_meals(body, Restaurant::Dormitory, date);
+
+    assert_eq!(meals.len(), 3);
+    assert_eq!(
+        meals[0],
+        Meal {
+            restaurant: Restaurant::Dormitory,
+            date,
+            time: MealTime::Lunch,
+            corner: Some("한식".to_string()),
+            menu: vec!["김치찌개".to_string(), "계란말이".to_string()],
+            price: Some(5000),
+        }
+    );
+    assert_eq!(meals[1].corner.as_deref(), Some("일품"));
+    assert_eq!(meals[1].price, Some(6000));
+    assert_eq!(meals[2].time, MealTime::Dinner);
+    assert_eq!(meals[2].corner, None);
+    assert_eq!(meals[2].price, None);
+}
@@ -0,0 +1,277 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// One step of a [`TitlePipeline`], applied in order.
+///
+/// `{writer}` stands for the notice's writer in the `text` of a `Literal` and the `replace` of a
+/// `Regex`. A `Regex`'s `pattern` is taken as is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TitleRule {
+    /// Replaces every occurrence of `text`
+    Literal {
+        text: String,
+        #[serde(default)]
+        replace: String,
+    },
+    /// Replaces every match of `pattern`, `$1` etc. refer to its groups
+    Regex {
+        pattern: String,
+        #[serde(default)]
+        replace: String,
+    },
+    /// Removes leading `[...]`, `(...)`, `【...】`, `<...>` groups, e.g. "[학사] [필독] 제목" -> "제목"
+    StripBracketPrefix,
+    /// Full-width ASCII and the ideographic space to their half-width forms
+    FullWidth,
+    /// Collapses runs of whitespace into one space and trims
+    Whitespace,
+    RemoveEmoji,
+    Trim,
+}
+
+enum CompiledRule {
+    Literal { text: String, replace: String },
+    Regex { regex: Regex, replace: String },
+    StripBracketPrefix,
+    FullWidth,
+    Whitespace,
+    RemoveEmoji,
+    Trim,
+}
+
+/// An ordered list of [`TitleRule`]s.
+pub struct TitlePipeline {
+    rules: Vec<CompiledRule>,
+}
+
+impl TitlePipeline {
+    pub fn new(rules: &[TitleRule]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                Ok(match rule {
+                    TitleRule::Literal { text, replace } => CompiledRule::Literal {
+                        text: text.clone(),
+                        replace: replace.clone(),
+                    },
+                    TitleRule::Regex { pattern, replace } => CompiledRule::Regex {
+                        regex: Regex::new(pattern)
+                            .with_context(|| format!("Invalid title rule regex {pattern}"))?,
+                        replace: replace.clone(),
+                    },
+                    TitleRule::StripBracketPrefix => CompiledRule::StripBracketPrefix,
+                    TitleRule::FullWidth => CompiledRule::FullWidth,
+                    TitleRule::Whitespace => CompiledRule::Whitespace,
+                    TitleRule::RemoveEmoji => CompiledRule::RemoveEmoji,
+                    TitleRule::Trim => CompiledRule::Trim,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(TitlePipeline { rules })
+    }
+
+    /// What `notice_parse` always did: drop "[writer]", " 자세히 보기" and "(재공지)", then trim.
+    pub fn default_rules() -> Vec<TitleRule> {
+        let remove = |text: &str| TitleRule::Literal {
+            text: text.to_string(),
+            replace: String::new(),
+        };
+
+        vec![
+            remove("[{writer}]"),
+            remove(" 자세히 보기"),
+            remove("(재공지)"),
+            TitleRule::Trim,
+        ]
+    }
+
+    pub fn apply(&self, raw_title: &str, writer: &str) -> String {
+        let mut title = raw_title.to_string();
+
+        for rule in &self.rules {
+            title = match rule {
+                CompiledRule::Literal { text, replace } => {
+                    title.replace(&text.replace("{writer}", writer), replace)
+                }
+                CompiledRule::Regex { regex, replace } => {
+                    let replace = replace.replace("{writer}", writer);
+                    regex.replace_all(&title, replace.as_str()).into_owned()
+                }
+                CompiledRule::StripBracketPrefix => strip_bracket_prefix(&title).to_string(),
+                CompiledRule::FullWidth => title.chars().map(to_half_width).collect(),
+                CompiledRule::Whitespace => title.split_whitespace().collect::<Vec<_>>().join(" "),
+                CompiledRule::RemoveEmoji => title.chars().filter(|c| !is_emoji(*c)).collect(),
+                CompiledRule::Trim => title.trim().to_string(),
+            };
+        }
+
+        title
+    }
+}
+
+impl Default for TitlePipeline {
+    fn default() -> Self {
+        TitlePipeline::new(&TitlePipeline::default_rules()).unwrap()
+    }
+}
+
+/// `TITLE_RULES` file: the default pipeline and overrides keyed by board URL.
+///
+/// ```json
+/// {
+///   "default": [{ "type": "literal", "text": "[{writer}]" }, { "type": "trim" }],
+///   "boards": {
+///     "https://www.ajou.ac.kr/kr/ajou/notice.do": [{ "type": "strip_bracket_prefix" }]
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TitleRulesConfig {
+    #[serde(default = "TitlePipeline::default_rules")]
+    pub default: Vec<TitleRule>,
+    #[serde(default)]
+    pub boards: HashMap<String, Vec<TitleRule>>,
+}
+
+/// Picks the [`TitlePipeline`] of each board.
+#[derive(Default)]
+pub struct TitleNormalizer {
+    default: TitlePipeline,
+    boards: HashMap<String, TitlePipeline>,
+}
+
+impl TitleNormalizer {
+    pub fn new(config: &TitleRulesConfig) -> Result<Self> {
+        Ok(TitleNormalizer {
+            default: TitlePipeline::new(&config.default)?,
+            boards: config
+                .boards
+                .iter()
+                .map(|(board, rules)| Ok((board.clone(), TitlePipeline::new(rules)?)))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Loaded from the JSON file at `TITLE_RULES` on first use, the built-in rules otherwise.
+    pub fn shared() -> Result<&'static TitleNormalizer> {
+        static SHARED: OnceLock<TitleNormalizer> = OnceLock::new();
+
+        if let Some(normalizer) = SHARED.get() {
+            return Ok(normalizer);
+        }
+        let normalizer = match std::env::var("TITLE_RULES") {
+            Ok(path) => {
+                let data =
+                    std::fs::read(&path).with_context(|| format!("Reading title rules {path}"))?;
+                let config: TitleRulesConfig = serde_json::from_slice(&data)
+                    .with_context(|| format!("Parsing title rules {path}"))?;
+                TitleNormalizer::new(&config)?
+            }
+            Err(_) => TitleNormalizer::default(),
+        };
+        Ok(SHARED.get_or_init(|| normalizer))
+    }
+
+    pub fn for_board(&self, board: &str) -> &TitlePipeline {
+        self.boards.get(board).unwrap_or(&self.default)
+    }
+}
+
+fn strip_bracket_prefix(mut title: &str) -> &str {
+    const PAIRS: [(char, char); 5] = [
+        ('[', ']'),
+        ('(', ')'),
+        ('【', '】'),
+        ('<', '>'),
+        ('〈', '〉'),
+    ];
+
+    loop {
+        title = title.trim_start();
+        let Some(first) = title.chars().next() else {
+            return title;
+        };
+        let Some((_, close)) = PAIRS.iter().find(|(open, _)| *open == first) else {
+            return title;
+        };
+        match title.find(*close) {
+            // Don't eat the whole title, e.g. "[공지]"
+            Some(end) if !title[end + close.len_utf8()..].trim().is_empty() => {
+                title = &title[end + close.len_utf8()..];
+            }
+            _ => return title,
+        }
+    }
+}
+
+fn to_half_width(c: char) -> char {
+    match c {
+        '\u{3000}' => ' ',
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        _ => c,
+    }
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32,
+        0x1F000..=0x1FAFF // pictographs, emoticons, transport, flags, ...
+        | 0x2600..=0x27BF // misc symbols, dingbats
+        | 0x2B50..=0x2B55 // stars and circles
+        | 0xFE00..=0xFE0F // variation selectors
+        | 0x200D          // zero width joiner
+        | 0x20E3          // keycap
+        | 0xE0020..=0xE007F) // tags
+}
+
+#[test]
+fn test_default_pipeline() {
+    let pipeline = TitlePipeline::default();
+
+    assert_eq!(
+        pipeline.apply("[학사팀] 2학기 수강신청 안내(재공지) 자세히 보기", "학사팀"),
+        "2학기 수강신청 안내"
+    );
+}
+
+#[test]
+fn test_custom_pipeline() {
+    let rules: Vec<TitleRule> = serde_json::from_str(
+        r#"[
+            { "type": "full_width" },
+            { "type": "remove_emoji" },
+            { "type": "strip_bracket_prefix" },
+            { "type": "regex", "pattern": "\\s*\\(~?\\d+/\\d+\\)$" },
+            { "type": "whitespace" }
+        ]"#,
+    )
+    .unwrap();
+    let pipeline = TitlePipeline::new(&rules).unwrap();
+
+    assert_eq!(
+        pipeline.apply("【필독】 [장학]　２０２３ 🎉 장학금   신청 (8/25)", ""),
+        "2023 장학금 신청"
+    );
+    assert_eq!(pipeline.apply("[공지]", ""), "[공지]");
+}
+
+#[test]
+fn test_writer_placeholder() {
+    let rules: Vec<TitleRule> = serde_json::from_str(
+        r#"[
+            { "type": "literal", "text": "[{writer}] " },
+            { "type": "regex", "pattern": "^(.*)$", "replace": "$1 ({writer})" }
+        ]"#,
+    )
+    .unwrap();
+    let pipeline = TitlePipeline::new(&rules).unwrap();
+
+    assert_eq!(
+        pipeline.apply("[학사팀] 수강신청 안내", "학사팀"),
+        "수강신청 안내 (학사팀)"
+    );
+}
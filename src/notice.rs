@@ -0,0 +1,290 @@
+use crate::deadline::{extract_deadlines, Deadline};
+use crate::department::Directory;
+use crate::http::HttpClient;
+use crate::normalize::{TitleNormalizer, TitlePipeline};
+use crate::{Notice, AJOU_LINK};
+use anyhow::Result;
+use chrono::Utc;
+use chrono_tz::Asia::Seoul;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use mongodb::bson::doc;
+use mongodb::{options::FindOptions, Collection};
+use scraper::{Html, Selector};
+use std::borrow::Cow;
+
+pub const DEFAULT_NUM_ARTICLES: usize = 7;
+
+/// Notice bodies read at once by [`add_body_deadlines`], the client still rate limiting them.
+const BODY_FETCH_CONCURRENCY: usize = 4;
+
+fn get_query(query_option: &str) -> Cow<'_, str> {
+    match query_option {
+        "ajou" => "?mode=list&article.offset=0&articleLimit=".into(),
+        _ => format!(
+            "?mode=list&srSearchKey=&srSearchVal={}&article.offset=0&articleLimit=",
+            query_option
+        )
+        .into(),
+    }
+}
+
+pub async fn notice_parse(query_option: &str, _nums: Option<usize>) -> Result<Vec<Notice>> {
+    notice_parse_board(AJOU_LINK, query_option, _nums).await
+}
+
+/// Same as [`notice_parse`] but for any board built on the same CMS (e.g. a department's notice.do).
+pub async fn notice_parse_board(
+    board: &str,
+    query_option: &str,
+    _nums: Option<usize>,
+) -> Result<Vec<Notice>> {
+    let query = get_query(query_option);
+    let nums_int = _nums.unwrap_or(DEFAULT_NUM_ARTICLES);
+
+    let url = [board, &query, &nums_int.to_string()].concat();
+
+    // Revalidated with ETag/Last-Modified, an unchanged board costs a 304 instead of the full page
+    let body = HttpClient::shared()?.get_text(&url).await?.body;
+    let pipeline = TitleNormalizer::shared()?.for_board(board);
+    let directory = Directory::shared()?;
+
+    let mut notices = parse_notice_list(&body, board, pipeline);
+    for notice in &mut notices {
+        notice.department = directory
+            .resolve(&notice.writer)
+            .map(|department| department.id.clone());
+        notice.deadlines = notice_deadlines(notice, "");
+    }
+
+    Ok(notices)
+}
+
+/// Parses a board list page, oldest notice first.
+pub fn parse_notice_list(body: &str, board: &str, pipeline: &TitlePipeline) -> Vec<Notice> {
+    let document = Html::parse_document(body);
+    let a_selector = Selector::parse("a").unwrap();
+
+    let ids = Selector::parse("td.b-num-box").unwrap();
+    let cates = Selector::parse("span.b-cate").unwrap();
+    let titles = Selector::parse("div.b-title-box").unwrap();
+    let dates = Selector::parse("span.b-date").unwrap();
+    let writers = Selector::parse("span.b-writer").unwrap();
+
+    let id_elements = document.select(&ids);
+    let mut cate_elements = document.select(&cates);
+    let mut title_elements = document.select(&titles);
+    let mut date_elements = document.select(&dates);
+    let mut writer_elements = document.select(&writers);
+
+    let mut notices: Vec<Notice> = id_elements
+        .filter_map(|id_element| {
+            let date = date_elements.next()?.text().next()?.trim().to_string();
+            let writer = writer_elements
+                .next()?
+                .text()
+                .next()
+                .unwrap_or("알 수 없음")
+                .trim()
+                .to_string();
+            let category = cate_elements.next()?.text().next()?.trim().to_string();
+            let inner_a = title_elements.next()?.select(&a_selector).next()?;
+            let id = id_element.text().next()?.trim().parse::<i32>().ok()?;
+
+            let raw_title = inner_a.value().attr("title")?.to_string();
+            let link = format!("{}{}", board, inner_a.value().attr("href")?);
+            let title = pipeline.apply(&raw_title, &writer);
+
+            Some(Notice {
+                id,
+                category,
+                title,
+                raw_title,
+                link,
+                date,
+                writer,
+                department: None,
+                deadlines: vec![],
+            })
+        })
+        .collect();
+
+    notices.reverse();
+
+    notices
+}
+
+/// Deadlines in the title and `body` of `notice`, resolved against its post date (today if unknown).
+pub fn notice_deadlines(notice: &Notice, body: &str) -> Vec<Deadline> {
+    let posted_on = notice
+        .posted_on()
+        .unwrap_or_else(|| Utc::now().with_timezone(&Seoul).date_naive());
+
+    // The normalized title may have lost e.g. "(~8/25)"
+    let title = if notice.raw_title.is_empty() {
+        &notice.title
+    } else {
+        &notice.raw_title
+    };
+
+    let mut deadlines = extract_deadlines(title, posted_on);
+    for deadline in extract_deadlines(body, posted_on) {
+        if !deadlines
+            .iter()
+            .any(|d| (d.start, d.end, d.time) == (deadline.start, deadline.end, deadline.time))
+        {
+            deadlines.push(deadline);
+        }
+    }
+    deadlines
+}
+
+/// Text of the notice at `link` (its view page).
+pub async fn notice_body(link: &str) -> Result<String> {
+    let body = HttpClient::shared()?.get_text(link).await?.body;
+    Ok(parse_notice_body(&body))
+}
+
+pub fn parse_notice_body(body: &str) -> String {
+    let document = Html::parse_document(body);
+    let content = Selector::parse("div.b-content-box").unwrap();
+
+    document
+        .select(&content)
+        .flat_map(|element| element.text())
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Reads the body of each notice to add its deadlines to the ones of the title.
+///
+/// A body that can't be read is skipped, the title's deadlines are kept.
+pub async fn add_body_deadlines(notices: &mut [Notice]) {
+    let reads: Vec<_> = notices.iter_mut().map(add_notice_body_deadlines).collect();
+    stream::iter(reads)
+        .buffer_unordered(BODY_FETCH_CONCURRENCY)
+        .collect::<()>()
+        .await;
+}
+
+async fn add_notice_body_deadlines(notice: &mut Notice) {
+    match notice_body(&notice.link).await {
+        Ok(body) => notice.deadlines = notice_deadlines(notice, &body),
+        Err(e) => eprintln!("Failed to read notice {}: {e}", notice.id),
+    }
+}
+
+/// Inserts the notices posted since the newest one in `notice_collection`, returning them.
+///
+/// With `dry_run`, nothing is written.
+pub async fn sync_notices(
+    notice_collection: &Collection<Notice>,
+    dry_run: bool,
+) -> Result<Vec<Notice>> {
+    let find_options = FindOptions::builder()
+        .sort(doc! { "id": -1})
+        .limit(1)
+        .build();
+
+    let last_db_notice = notice_collection
+        .find(doc! {}, find_options)
+        .await?
+        .try_next()
+        .await?;
+
+    // An unchanged first page costs a 304, the comparison below still catching notices a failed
+    // sync didn't store
+    let parsed_notice = notice_parse("ajou", Some(1)).await?;
+
+    let last_parsed_notice_id = match parsed_notice.first() {
+        Some(notice) => notice.id,
+        None => return Ok(vec![]),
+    };
+
+    let num_missing_notices = match last_db_notice {
+        Some(notice) => (last_parsed_notice_id - notice.id).max(0) as usize,
+        // Empty collection, start from the first page
+        None => DEFAULT_NUM_ARTICLES,
+    };
+
+    let mut parsed_notices = if num_missing_notices != 0 {
+        notice_parse("ajou", Some(num_missing_notices)).await?
+    } else {
+        vec![]
+    };
+    add_body_deadlines(&mut parsed_notices).await;
+
+    if !parsed_notices.is_empty() && !dry_run {
+        notice_collection
+            .insert_many(parsed_notices.clone(), None)
+            .await?;
+    }
+
+    Ok(parsed_notices)
+}
+
+/// Parses the latest `nums` notices and inserts the ones whose id isn't stored yet.
+///
+/// With `dry_run`, nothing is written.
+pub async fn backfill_notices(
+    notice_collection: &Collection<Notice>,
+    nums: usize,
+    dry_run: bool,
+) -> Result<Vec<Notice>> {
+    let parsed_notices = notice_parse("ajou", Some(nums)).await?;
+    let ids: Vec<i32> = parsed_notices.iter().map(|notice| notice.id).collect();
+
+    let stored: Vec<Notice> = notice_collection
+        .find(doc! { "id": { "$in": ids } }, None)
+        .await?
+        .try_collect()
+        .await?;
+
+    let mut missing: Vec<Notice> = parsed_notices
+        .into_iter()
+        .filter(|notice| !stored.iter().any(|s| s.id == notice.id))
+        .collect();
+    add_body_deadlines(&mut missing).await;
+
+    if !missing.is_empty() && !dry_run {
+        notice_collection.insert_many(missing.clone(), None).await?;
+    }
+
+    Ok(missing)
+}
+
+/// Resolves the writer of every stored notice again, e.g. after adding an alias to the directory.
+///
+/// Returns the number of notices whose department changed. With `dry_run`, nothing is written.
+pub async fn resolve_departments(
+    notice_collection: &Collection<Notice>,
+    dry_run: bool,
+) -> Result<u64> {
+    let directory = Directory::shared()?;
+    let writers = notice_collection.distinct("writer", None, None).await?;
+
+    let mut changed = 0;
+    for writer in writers.iter().filter_map(|writer| writer.as_str()) {
+        let department = directory.resolve(writer).map(|department| &department.id);
+        let filter = doc! { "writer": writer, "department": { "$ne": department } };
+
+        changed += if dry_run {
+            notice_collection.count_documents(filter, None).await?
+        } else {
+            notice_collection
+                .update_many(filter, doc! { "$set": { "department": department } }, None)
+                .await?
+                .modified_count
+        };
+    }
+
+    Ok(changed)
+}
+
+#[tokio::test]
+async fn test_parse_notice() {
+    let notices = notice_parse("ajou", Some(7)).await.unwrap();
+
+    println!("{:#?}", notices);
+}
@@ -0,0 +1,217 @@
+use crate::building::Campus;
+use crate::course::{self, Course, CourseCategory, CourseStorage, Semester};
+use anyhow::Result;
+use chrono::{NaiveTime, Weekday};
+use mongodb::bson::doc;
+use mongodb::Client;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// A room taken by a course once a week.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Booking {
+    pub weekday: Weekday,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub subject_code: String,
+    pub class_number: String,
+    pub subject_name: String,
+}
+
+/// A room free at some time, and until when.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FreeRoom {
+    pub room: String,
+    /// Start of the next booking that day, `None` if it stays free
+    pub until: Option<NaiveTime>,
+}
+
+/// Weekly bookings of every room courses meet in, by (building id, room).
+#[derive(Debug, Default)]
+pub struct OccupancyIndex {
+    rooms: BTreeMap<(String, String), Vec<Booking>>,
+}
+
+impl OccupancyIndex {
+    /// Indexes the offline slots of `courses` whose classroom resolves to a building and a room.
+    ///
+    /// Courses stored before slots were parsed have theirs parsed with `campus`.
+    pub fn new(courses: &[Course], campus: &Campus) -> Self {
+        let mut rooms: BTreeMap<(String, String), Vec<Booking>> = BTreeMap::new();
+
+        for course in courses {
+            let parsed;
+            let slots = if course.time_slots.is_empty() {
+                parsed = course.parse_time_slots(campus);
+                &parsed
+            } else {
+                &course.time_slots
+            };
+
+            for slot in slots.iter().filter(|slot| !slot.online) {
+                let Some(classroom) = &slot.classroom else {
+                    continue;
+                };
+                let Some(room) = &classroom.room else {
+                    continue;
+                };
+
+                let booking = Booking {
+                    weekday: slot.weekday,
+                    start: slot.start,
+                    end: slot.end,
+                    subject_code: course.subject_code.clone(),
+                    class_number: course.class_number.clone(),
+                    subject_name: course.subject_korean_name.clone(),
+                };
+                // The same course is listed under several categories
+                let bookings = rooms
+                    .entry((classroom.building.clone(), room.clone()))
+                    .or_default();
+                if !bookings.contains(&booking) {
+                    bookings.push(booking);
+                }
+            }
+        }
+
+        for bookings in rooms.values_mut() {
+            bookings.sort_by_key(|booking| (booking.weekday.num_days_from_monday(), booking.start));
+        }
+        OccupancyIndex { rooms }
+    }
+
+    /// Built from the stored courses of every category.
+    pub async fn load(
+        client: &Client,
+        storage: CourseStorage,
+        semester: &Semester,
+    ) -> Result<Self> {
+        let courses =
+            course::find_courses(client, storage, semester, &CourseCategory::ALL, doc! {}).await?;
+        Ok(OccupancyIndex::new(&courses, Campus::shared()?))
+    }
+
+    /// Rooms of `building` any course meets in.
+    pub fn rooms(&self, building: &str) -> Vec<&str> {
+        self.rooms
+            .keys()
+            .filter(|(id, _)| id == building)
+            .map(|(_, room)| room.as_str())
+            .collect()
+    }
+
+    fn bookings(&self, building: &str, room: &str) -> &[Booking] {
+        self.rooms
+            .get(&(building.to_string(), room.to_string()))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Rooms of `building` no course is in at `time` on `weekday`.
+    pub fn free_rooms(&self, building: &str, weekday: Weekday, time: NaiveTime) -> Vec<FreeRoom> {
+        self.rooms(building)
+            .into_iter()
+            .filter_map(|room| {
+                let mut today = self
+                    .bookings(building, room)
+                    .iter()
+                    .filter(|booking| booking.weekday == weekday);
+                if today
+                    .clone()
+                    .any(|booking| booking.start <= time && time < booking.end)
+                {
+                    return None;
+                }
+                Some(FreeRoom {
+                    room: room.to_string(),
+                    until: today
+                        .find(|booking| booking.start > time)
+                        .map(|booking| booking.start),
+                })
+            })
+            .collect()
+    }
+
+    /// The booking going on at `time` on `weekday` or the next one, wrapping around the week.
+    pub fn next_occupied(
+        &self,
+        building: &str,
+        room: &str,
+        weekday: Weekday,
+        time: NaiveTime,
+    ) -> Option<&Booking> {
+        let bookings = self.bookings(building, room);
+        let today = weekday.num_days_from_monday();
+        let later = |booking: &&Booking| {
+            let day = booking.weekday.num_days_from_monday();
+            day > today || (day == today && booking.end > time)
+        };
+        bookings.iter().find(later).or_else(|| bookings.first())
+    }
+
+    /// Bookings of a room by day, Monday first.
+    pub fn week(&self, building: &str, room: &str) -> [Vec<&Booking>; 7] {
+        let mut week: [Vec<&Booking>; 7] = Default::default();
+        for booking in self.bookings(building, room) {
+            week[booking.weekday.num_days_from_monday() as usize].push(booking);
+        }
+        week
+    }
+}
+
+#[test]
+fn test_occupancy() {
+    use crate::building::default_buildings;
+
+    let campus = Campus::new(default_buildings()).unwrap();
+    let course = |code: &str, time: &str| Course {
+        subject_code: code.to_string(),
+        class_number: "1".to_string(),
+        subject_korean_name: code.to_string(),
+        class_time_korean: time.to_string(),
+        ..Default::default()
+    };
+    let courses = vec![
+        course(
+            "X101",
+            "월 10:30-11:45(팔달관 309) 수 10:30-11:45(팔달관 309)",
+        ),
+        course("X102", "월 13:30-14:45(팔309)"),
+        course("X103", "월 09:00-10:15(팔달관 310)"),
+        course("X104", "화 09:00-10:15(온라인)"),
+        // Listed again under another category
+        course("X103", "월 09:00-10:15(팔달관 310)"),
+    ];
+    let index = OccupancyIndex::new(&courses, &campus);
+    let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+
+    assert_eq!(index.rooms("paldal"), vec!["309", "310"]);
+
+    assert_eq!(
+        index.free_rooms("paldal", Weekday::Mon, time(12, 0)),
+        vec![
+            FreeRoom {
+                room: "309".to_string(),
+                until: Some(time(13, 30)),
+            },
+            FreeRoom {
+                room: "310".to_string(),
+                until: None,
+            },
+        ]
+    );
+    assert_eq!(
+        index.free_rooms("paldal", Weekday::Mon, time(9, 30)).len(),
+        1
+    );
+
+    let next = index.next_occupied("paldal", "309", Weekday::Mon, time(15, 0));
+    assert_eq!(next.unwrap().weekday, Weekday::Wed);
+    let next = index.next_occupied("paldal", "309", Weekday::Thu, time(9, 0));
+    assert_eq!(next.unwrap().start, time(10, 30));
+    assert_eq!(next.unwrap().weekday, Weekday::Mon);
+
+    let week = index.week("paldal", "309");
+    assert_eq!(week[0].len(), 2);
+    assert_eq!(week[2][0].subject_code, "X101");
+    assert_eq!(index.week("paldal", "310")[0].len(), 1);
+}
@@ -0,0 +1,308 @@
+use crate::http::HttpClient;
+use anyhow::Result;
+use clap::ValueEnum;
+use reqwest::Url;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Staff and faculty phone directory, searched with `srSearchKey`/`srSearchVal`.
+pub const PEOPLE_LINK: &str = "https://www.ajou.ac.kr/kr/guide/phone.do";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum SearchBy {
+    Name,
+    Department,
+    /// Phone number or extension, e.g. 2114 or 031-219-2114
+    Phone,
+}
+
+impl SearchBy {
+    fn key(self) -> &'static str {
+        match self {
+            SearchBy::Name => "name",
+            SearchBy::Department => "dept",
+            SearchBy::Phone => "tel",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Person {
+    pub name: String,
+    pub department: Option<String>,
+    pub position: Option<String>,
+    pub phone: Option<String>,
+    pub email: Option<String>,
+    /// Building and room, e.g. "팔달관 301호"
+    pub office: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum FieldPolicy {
+    #[default]
+    Show,
+    /// Partly hidden: the last digits of a phone, most of an email's local part, the room number
+    Mask,
+    Hide,
+}
+
+/// What a [`Person`] may reveal, e.g. in chatbot answers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Redaction {
+    pub phone: FieldPolicy,
+    pub email: FieldPolicy,
+    pub office: FieldPolicy,
+}
+
+impl Redaction {
+    /// Office phones stay reachable, emails and rooms aren't handed out to anyone asking.
+    pub fn chatbot() -> Self {
+        Redaction {
+            phone: FieldPolicy::Show,
+            email: FieldPolicy::Mask,
+            office: FieldPolicy::Mask,
+        }
+    }
+
+    pub fn hide_contacts() -> Self {
+        Redaction {
+            phone: FieldPolicy::Hide,
+            email: FieldPolicy::Hide,
+            office: FieldPolicy::Hide,
+        }
+    }
+}
+
+impl Person {
+    pub fn redacted(&self, redaction: &Redaction) -> Person {
+        let apply =
+            |value: &Option<String>, policy: FieldPolicy, mask: fn(&str) -> String| match policy {
+                FieldPolicy::Show => value.clone(),
+                FieldPolicy::Mask => value.as_deref().map(mask),
+                FieldPolicy::Hide => None,
+            };
+
+        Person {
+            phone: apply(&self.phone, redaction.phone, mask_phone),
+            email: apply(&self.email, redaction.email, mask_email),
+            office: apply(&self.office, redaction.office, mask_office),
+            ..self.clone()
+        }
+    }
+}
+
+/// "031-219-2114" -> "031-219-****"
+fn mask_phone(phone: &str) -> String {
+    let digits = phone.chars().filter(char::is_ascii_digit).count();
+    let mut to_mask = digits.min(4);
+    let mut masked: Vec<char> = phone.chars().collect();
+    for c in masked.iter_mut().rev() {
+        if to_mask == 0 {
+            break;
+        }
+        if c.is_ascii_digit() {
+            *c = '*';
+            to_mask -= 1;
+        }
+    }
+    masked.into_iter().collect()
+}
+
+/// "gildong@ajou.ac.kr" -> "gi***@ajou.ac.kr"
+fn mask_email(email: &str) -> String {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            let shown: String = local.chars().take(2).collect();
+            format!("{shown}***@{domain}")
+        }
+        None => "***".to_string(),
+    }
+}
+
+/// "팔달관 301호" -> "팔달관"
+fn mask_office(office: &str) -> String {
+    office
+        .split_whitespace()
+        .take_while(|part| !part.chars().any(|c| c.is_ascii_digit()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Results by search, with when they were fetched.
+type Cache = HashMap<(SearchBy, String), (Instant, Vec<Person>)>;
+
+/// Searches the directory, remembering results for `ttl`.
+pub struct PeopleClient {
+    link: String,
+    ttl: Duration,
+    cache: Mutex<Cache>,
+}
+
+impl Default for PeopleClient {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(3600))
+    }
+}
+
+impl PeopleClient {
+    /// `PEOPLE_SEARCH` overrides the directory URL.
+    pub fn new(ttl: Duration) -> Self {
+        PeopleClient {
+            link: std::env::var("PEOPLE_SEARCH").unwrap_or_else(|_| PEOPLE_LINK.to_string()),
+            ttl,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn search(&self, by: SearchBy, query: &str) -> Result<Vec<Person>> {
+        let query = query.trim().to_string();
+        if let Some((at, people)) = self.cache.lock().unwrap().get(&(by, query.clone())) {
+            if at.elapsed() < self.ttl {
+                return Ok(people.clone());
+            }
+        }
+
+        let url = Url::parse_with_params(
+            &self.link,
+            [
+                ("mode", "list"),
+                ("srSearchKey", by.key()),
+                ("srSearchVal", &query),
+            ],
+        )?;
+        // Names, phones and emails stay out of the shared (possibly on-disk) response cache
+        let body = HttpClient::shared()?.get_uncached(url.as_str()).await?;
+        let people = parse_people(&body);
+
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|_, (at, _)| at.elapsed() < self.ttl);
+        cache.insert((by, query), (Instant::now(), people.clone()));
+        Ok(people)
+    }
+
+    /// Same as [`PeopleClient::search`], with `redaction` applied to every result.
+    pub async fn search_redacted(
+        &self,
+        by: SearchBy,
+        query: &str,
+        redaction: &Redaction,
+    ) -> Result<Vec<Person>> {
+        Ok(self
+            .search(by, query)
+            .await?
+            .iter()
+            .map(|person| person.redacted(redaction))
+            .collect())
+    }
+}
+
+/// Reads the result table, finding each column from its header.
+pub fn parse_people(body: &str) -> Vec<Person> {
+    let document = Html::parse_document(body);
+    let headers = Selector::parse("thead th").unwrap();
+    let rows = Selector::parse("tbody tr").unwrap();
+    let cells = Selector::parse("td").unwrap();
+
+    let text = |element: ElementRef| -> String {
+        element
+            .text()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let columns: Vec<Option<Column>> = document
+        .select(&headers)
+        .map(|header| Column::from_header(&text(header)))
+        .collect();
+
+    document
+        .select(&rows)
+        .filter_map(|row| {
+            // e.g. a single "검색 결과가 없습니다" cell
+            if row.select(&cells).count() < columns.len() {
+                return None;
+            }
+
+            let mut person = Person::default();
+            for (column, cell) in columns.iter().zip(row.select(&cells)) {
+                let value = Some(text(cell)).filter(|value| !value.is_empty() && value != "-");
+                match column {
+                    Some(Column::Name) => person.name = value.unwrap_or_default(),
+                    Some(Column::Department) => person.department = value,
+                    Some(Column::Position) => person.position = value,
+                    Some(Column::Phone) => person.phone = value,
+                    Some(Column::Email) => person.email = value,
+                    Some(Column::Office) => person.office = value,
+                    None => {}
+                }
+            }
+            (!person.name.is_empty()).then_some(person)
+        })
+        .collect()
+}
+
+enum Column {
+    Name,
+    Department,
+    Position,
+    Phone,
+    Email,
+    Office,
+}
+
+impl Column {
+    fn from_header(header: &str) -> Option<Self> {
+        let has = |words: &[&str]| words.iter().any(|word| header.contains(word));
+        if has(&["성명", "이름"]) {
+            Some(Column::Name)
+        } else if has(&["소속", "부서", "학과"]) {
+            Some(Column::Department)
+        } else if has(&["직위", "직급", "직책"]) {
+            Some(Column::Position)
+        } else if has(&["전화", "연락처", "내선"]) {
+            Some(Column::Phone)
+        } else if has(&["메일", "mail", "Mail"]) {
+            Some(Column::Email)
+        } else if has(&["위치", "사무실", "연구실"]) {
+            Some(Column::Office)
+        } else {
+            None
+        }
+    }
+}
+
+#[test]
+fn test_parse_people() {
+    let body = r#"
+        <table>
+            <thead><tr><th>성명</th><th>소속</th><th>직위</th><th>전화번호</th><th>E-mail</th><th>위치</th></tr></thead>
+            <tbody>
+                <tr><td>홍길동</td><td>학사팀</td><td>팀장</td><td>031-219-2114</td><td>gildong@ajou.ac.kr</td><td>율곡관 101호</td></tr>
+                <tr><td>김아주</td><td>소프트웨어학과</td><td>교수</td><td>-</td><td></td><td>팔달관 301호</td></tr>
+                <tr><td colspan="6">검색 결과가 없습니다</td></tr>
+            </tbody>
+        </table>
+    "#;
+    let people = parse_people(body);
+
+    assert_eq!(people.len(), 2);
+    assert_eq!(people[0].department.as_deref(), Some("학사팀"));
+    assert_eq!(people[1].phone, None);
+
+    let redacted = people[0].redacted(&Redaction::chatbot());
+    assert_eq!(redacted.phone.as_deref(), Some("031-219-2114"));
+    assert_eq!(redacted.email.as_deref(), Some("gi***@ajou.ac.kr"));
+    assert_eq!(redacted.office.as_deref(), Some("율곡관"));
+
+    let hidden = people[0].redacted(&Redaction {
+        phone: FieldPolicy::Mask,
+        ..Redaction::hide_contacts()
+    });
+    assert_eq!(hidden.phone.as_deref(), Some("031-219-****"));
+    assert_eq!(hidden.email, None);
+}
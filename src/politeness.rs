@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::time::{sleep, Duration, Instant};
+
+/// Per-host token buckets: `rate` requests per second on average, bursts of up to `burst`.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+    /// `Crawl-delay` from robots.txt, overrides `rate` when slower
+    min_interval: Option<Duration>,
+}
+
+impl Bucket {
+    /// Takes a token, returning how long to wait before it can be used.
+    fn take(&mut self, now: Instant, rate: f64, burst: f64) -> Duration {
+        let rate = match self.min_interval {
+            Some(interval) if !interval.is_zero() => rate.min(1.0 / interval.as_secs_f64()),
+            _ => rate,
+        };
+        let burst = if self.min_interval.is_some() {
+            1.0
+        } else {
+            burst
+        };
+
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst);
+        self.last = now;
+        self.tokens -= 1.0;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: u32) -> Self {
+        RateLimiter {
+            rate: rate.max(f64::MIN_POSITIVE),
+            burst: burst.max(1) as f64,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits until a request to `host` is allowed.
+    pub async fn acquire(&self, host: &str) {
+        let wait = {
+            let mut buckets = self.buckets.lock().unwrap();
+            let bucket = buckets.entry(host.to_string()).or_insert_with(|| Bucket {
+                tokens: self.burst,
+                last: Instant::now(),
+                min_interval: None,
+            });
+            bucket.take(Instant::now(), self.rate, self.burst)
+        };
+
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+    }
+
+    pub fn set_crawl_delay(&self, host: &str, delay: Duration) {
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(host.to_string()).or_insert_with(|| Bucket {
+            tokens: 1.0,
+            last: Instant::now(),
+            min_interval: None,
+        });
+        bucket.min_interval = Some(delay);
+    }
+}
+
+/// The rules of a robots.txt that apply to us (the `*` group).
+#[derive(Debug, Default, Clone)]
+pub struct Robots {
+    /// (allow, path prefix)
+    rules: Vec<(bool, String)>,
+    pub crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    pub fn parse(body: &str) -> Self {
+        let mut robots = Robots::default();
+        // Whether the current group is for `*`, and whether we're still reading its User-agent lines
+        let mut in_group = false;
+        let mut reading_agents = false;
+
+        for line in body.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if !reading_agents {
+                        in_group = false;
+                        reading_agents = true;
+                    }
+                    in_group |= value == "*";
+                }
+                "allow" | "disallow" if in_group => {
+                    reading_agents = false;
+                    // An empty Disallow allows everything
+                    if !value.is_empty() {
+                        robots
+                            .rules
+                            .push((key.trim().eq_ignore_ascii_case("allow"), value.to_string()));
+                    }
+                }
+                "crawl-delay" if in_group => {
+                    reading_agents = false;
+                    robots.crawl_delay = value.parse::<f64>().ok().map(Duration::from_secs_f64);
+                }
+                _ => reading_agents = false,
+            }
+        }
+
+        robots
+    }
+
+    /// The longest matching rule wins, Allow on ties. Supports `*` and `$` in rules.
+    pub fn is_allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, rule)| rule_matches(rule, path))
+            .max_by_key(|(allow, rule)| (rule.len(), *allow))
+            .is_none_or(|(allow, _)| *allow)
+    }
+}
+
+fn rule_matches(rule: &str, path: &str) -> bool {
+    let (rule, anchored) = match rule.strip_suffix('$') {
+        Some(rule) => (rule, true),
+        None => (rule, false),
+    };
+
+    let mut parts: Vec<&str> = rule.split('*').collect();
+    let last = if anchored && parts.len() > 1 {
+        parts.pop()
+    } else {
+        None
+    };
+
+    let Some(mut rest) = path.strip_prefix(parts[0]) else {
+        return false;
+    };
+    for part in &parts[1..] {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+
+    match last {
+        Some(last) => rest.ends_with(last),
+        None => !anchored || rest.is_empty(),
+    }
+}
+
+#[test]
+fn test_robots() {
+    let robots = Robots::parse(
+        "User-agent: Googlebot\n\
+         Disallow: /\n\
+         \n\
+         User-agent: *\n\
+         Disallow: /kr/ajou/notice.do # comment\n\
+         Allow: /kr/ajou/notice.do?mode=list\n\
+         Disallow: /*.pdf$\n\
+         Crawl-delay: 2\n",
+    );
+
+    assert!(robots.is_allowed("/kr/index.do"));
+    assert!(!robots.is_allowed("/kr/ajou/notice.do?mode=view"));
+    assert!(robots.is_allowed("/kr/ajou/notice.do?mode=list&articleLimit=7"));
+    assert!(!robots.is_allowed("/files/a.pdf"));
+    assert!(robots.is_allowed("/files/a.pdf?x=1"));
+    assert!(!robots.is_allowed("/files/a.pdf.pdf"));
+    assert_eq!(robots.crawl_delay, Some(Duration::from_secs(2)));
+}
+
+#[test]
+fn test_bucket() {
+    let now = Instant::now();
+    let mut bucket = Bucket {
+        tokens: 2.0,
+        last: now,
+        min_interval: None,
+    };
+
+    assert_eq!(bucket.take(now, 1.0, 2.0), Duration::ZERO);
+    assert_eq!(bucket.take(now, 1.0, 2.0), Duration::ZERO);
+    assert_eq!(bucket.take(now, 1.0, 2.0), Duration::from_secs(1));
+    // Refilled after a while
+    assert_eq!(
+        bucket.take(now + Duration::from_secs(10), 1.0, 2.0),
+        Duration::ZERO
+    );
+}
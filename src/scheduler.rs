@@ -0,0 +1,530 @@
+use crate::calendar::{self, AcademicEvent};
+use crate::digest::{self, DigestPeriod, MailConfig};
+use crate::export::NoticeQuery;
+use crate::ics;
+use crate::notice::sync_notices;
+use crate::Notice;
+use anyhow::Result;
+use chrono::{DateTime, Datelike, TimeZone, Timelike, Utc, Weekday};
+use chrono_tz::Asia::Seoul;
+use chrono_tz::Tz;
+use mongodb::Collection;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{watch, Notify};
+use tokio::time::{sleep, Duration};
+
+/// Why [`Scheduler::sleep_until`] returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wake {
+    /// The deadline passed.
+    Elapsed,
+    /// Someone asked for an immediate sync.
+    Triggered,
+    /// SIGINT/SIGTERM (or [`Scheduler::shutdown`]) was received.
+    Shutdown,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Status {
+    pub paused: bool,
+    pub next_wake: Option<DateTime<Tz>>,
+    pub last_sync: Option<DateTime<Tz>>,
+}
+
+/// Shared state between the updater loop and its control interface.
+///
+/// The loop only ever sleeps through [`Scheduler::sleep_until`], so a sync request,
+/// pause/resume or shutdown interrupts the wait instead of the running cycle.
+pub struct Scheduler {
+    status: Mutex<Status>,
+    /// Held while syncing, so the loop and the digests never insert the same notices twice
+    syncing: tokio::sync::Mutex<()>,
+    sync_requested: AtomicBool,
+    changed: Notify,
+    shutdown: watch::Sender<bool>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler {
+            status: Mutex::new(Status::default()),
+            syncing: tokio::sync::Mutex::new(()),
+            sync_requested: AtomicBool::new(false),
+            changed: Notify::new(),
+            shutdown: watch::channel(false).0,
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.status.lock().unwrap().paused
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        *self.shutdown.borrow()
+    }
+
+    /// Wakes the loop up and runs a cycle now, even while paused or outside working hours.
+    pub fn trigger_sync(&self) {
+        self.sync_requested.store(true, Ordering::SeqCst);
+        self.changed.notify_one();
+    }
+
+    pub fn pause(&self) {
+        self.status.lock().unwrap().paused = true;
+        self.changed.notify_one();
+    }
+
+    pub fn resume(&self) {
+        self.status.lock().unwrap().paused = false;
+        self.changed.notify_one();
+    }
+
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    pub fn mark_synced(&self, at: DateTime<Tz>) {
+        self.status.lock().unwrap().last_sync = Some(at);
+    }
+
+    /// Runs [`sync_notices`], one sync at a time.
+    pub async fn sync(&self, notice_collection: &Collection<Notice>) -> Result<Vec<Notice>> {
+        let _syncing = self.syncing.lock().await;
+        let result = sync_notices(notice_collection, false).await;
+        self.mark_synced(Utc::now().with_timezone(&Seoul));
+        result
+    }
+
+    /// Sleeps until `deadline` unless a sync is requested or a shutdown is received first.
+    ///
+    /// While paused the deadline is ignored, only a manual sync or shutdown ends the wait.
+    pub async fn sleep_until(&self, deadline: DateTime<Tz>) -> Wake {
+        let mut shutdown = self.shutdown.subscribe();
+
+        loop {
+            if *shutdown.borrow_and_update() {
+                return Wake::Shutdown;
+            }
+            if self.sync_requested.swap(false, Ordering::SeqCst) {
+                self.status.lock().unwrap().next_wake = None;
+                return Wake::Triggered;
+            }
+
+            let paused = {
+                let mut status = self.status.lock().unwrap();
+                status.next_wake = (!status.paused).then_some(deadline);
+                status.paused
+            };
+            let remaining = (deadline.with_timezone(&Utc) - Utc::now())
+                .to_std()
+                .unwrap_or(Duration::ZERO);
+
+            tokio::select! {
+                _ = sleep(remaining), if !paused => {
+                    self.status.lock().unwrap().next_wake = None;
+                    return Wake::Elapsed;
+                }
+                _ = self.changed.notified() => {}
+                _ = shutdown.changed() => {}
+            }
+        }
+    }
+
+    /// Sleeps until `deadline` unless a shutdown is received first, for jobs running beside the loop.
+    ///
+    /// Unlike [`Scheduler::sleep_until`], pause and sync requests are ignored and the status isn't touched.
+    pub async fn wait_until(&self, deadline: DateTime<Tz>) -> Wake {
+        let mut shutdown = self.shutdown.subscribe();
+        if *shutdown.borrow_and_update() {
+            return Wake::Shutdown;
+        }
+
+        let remaining = (deadline.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO);
+        tokio::select! {
+            _ = sleep(remaining) => Wake::Elapsed,
+            _ = shutdown.changed() => Wake::Shutdown,
+        }
+    }
+}
+
+/// Returns when the loop should wake up next, or `None` during working hours (weekdays 9am-7pm KST).
+pub fn next_active_time(seoul_now: DateTime<Tz>) -> Option<DateTime<Tz>> {
+    match seoul_now.weekday() {
+        Weekday::Sat | Weekday::Sun => return Some(next_weekday(seoul_now)),
+        _ => {}
+    }
+
+    if seoul_now.hour() >= 19 || seoul_now.hour() <= 8 {
+        return Some(next_morning(seoul_now));
+    }
+
+    None
+}
+
+/// Next KST 9:30am; today's if it's still early morning.
+pub fn next_morning(seoul_now: DateTime<Tz>) -> DateTime<Tz> {
+    let mut next_morning = Seoul
+        .with_ymd_and_hms(
+            seoul_now.year(),
+            seoul_now.month(),
+            seoul_now.day(),
+            9,
+            30,
+            0,
+        )
+        .unwrap();
+
+    if seoul_now.hour() >= 19 {
+        next_morning += chrono::Duration::days(1);
+    }
+
+    next_morning
+}
+
+/// Next KST Monday 9am.
+pub fn next_weekday(seoul_now: DateTime<Tz>) -> DateTime<Tz> {
+    Seoul
+        .with_ymd_and_hms(
+            seoul_now.year(),
+            seoul_now.month(),
+            seoul_now.day(),
+            9,
+            0,
+            0,
+        )
+        .unwrap()
+        + chrono::Duration::days(7 - seoul_now.weekday().num_days_from_monday() as i64)
+}
+
+/// Next KST 8am, when the digests of the previous day (and week, on Mondays) go out.
+pub fn next_digest_time(seoul_now: DateTime<Tz>) -> DateTime<Tz> {
+    let today = Seoul
+        .with_ymd_and_hms(
+            seoul_now.year(),
+            seoul_now.month(),
+            seoul_now.day(),
+            8,
+            0,
+            0,
+        )
+        .unwrap();
+
+    if seoul_now < today {
+        today
+    } else {
+        today + chrono::Duration::days(1)
+    }
+}
+
+/// Calls [`Scheduler::shutdown`] on the first SIGINT/SIGTERM and exits on the second one.
+pub fn handle_signals(scheduler: Arc<Scheduler>) {
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        println!("Shutting down after the current cycle... (send again to force)");
+        scheduler.shutdown();
+
+        wait_for_signal().await;
+        println!("Forced shutdown");
+        std::process::exit(130);
+    });
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut term = signal(SignalKind::terminate()).expect("Failed to install SIGTERM handler");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = term.recv() => {},
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    tokio::signal::ctrl_c()
+        .await
+        .expect("Failed to install Ctrl-C handler");
+}
+
+/// Serves line commands (`sync`, `pause`, `resume`, `status`) on a Unix socket.
+///
+/// e.g. `echo sync | nc -U updater.sock`
+#[cfg(unix)]
+pub async fn serve_control(
+    scheduler: Arc<Scheduler>,
+    path: impl AsRef<std::path::Path>,
+) -> std::io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixListener;
+
+    let path = path.as_ref();
+    // Left over from a previous run that didn't shut down cleanly, anything else is left alone
+    // for `bind` to fail on
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    let listener = UnixListener::bind(path)?;
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let scheduler = scheduler.clone();
+
+        tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                let reply = handle_command(&scheduler, line.trim());
+                if writer.write_all(reply.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+fn handle_command(scheduler: &Scheduler, command: &str) -> String {
+    match command {
+        "sync" => {
+            scheduler.trigger_sync();
+            "ok: sync queued\n".into()
+        }
+        "pause" => {
+            scheduler.pause();
+            "ok: paused\n".into()
+        }
+        "resume" => {
+            scheduler.resume();
+            "ok: resumed\n".into()
+        }
+        "status" => {
+            let status = scheduler.status();
+            let fmt = |t: Option<DateTime<Tz>>| t.map_or("-".to_string(), |t| t.to_rfc3339());
+            format!(
+                "paused: {}\nnext_wake: {}\nlast_sync: {}\n",
+                status.paused,
+                fmt(status.next_wake),
+                fmt(status.last_sync)
+            )
+        }
+        other => format!("error: unknown command '{other}' (sync|pause|resume|status)\n"),
+    }
+}
+
+/// The `updater` loop: syncs notices every 30 minutes during working hours until SIGINT/SIGTERM.
+///
+/// Also serves the control socket at `UPDATER_CONTROL` (default `updater.sock`),
+/// and rewrites the calendar at `ICS_FILE` after each sync if set.
+/// During breaks of the academic calendar, it rests 3 hours between syncs instead.
+pub async fn run_updater(
+    notice_collection: &Collection<Notice>,
+    calendar_collection: &Collection<AcademicEvent>,
+) {
+    let scheduler = Arc::new(Scheduler::new());
+    handle_signals(scheduler.clone());
+
+    #[cfg(unix)]
+    {
+        let control_path =
+            std::env::var("UPDATER_CONTROL").unwrap_or_else(|_| "updater.sock".to_string());
+        let scheduler = scheduler.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_control(scheduler, &control_path).await {
+                println!("Control socket {control_path} stopped: {e}");
+            }
+        });
+    }
+
+    let digests = match (MailConfig::from_env(), digest::scheduled_periods()) {
+        (Ok(Some(mail)), Ok(periods)) => Some(tokio::spawn(run_digests(
+            notice_collection.clone(),
+            scheduler.clone(),
+            mail,
+            periods,
+        ))),
+        (Ok(None), _) => {
+            println!("SMTP_HOST isn't set, no digests will be sent");
+            None
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            println!("Digests disabled: {e}");
+            None
+        }
+    };
+
+    'main: loop {
+        let seoul_now: DateTime<Tz> = Utc::now().with_timezone(&Seoul);
+
+        if let Some(wake_at) = next_active_time(seoul_now) {
+            let difference = (wake_at - seoul_now).num_seconds();
+            match seoul_now.weekday() {
+                Weekday::Sat | Weekday::Sun => println!(
+                    "Weekend...resting until next KST Monday 9am: {} seconds",
+                    difference
+                ),
+                _ => println!(
+                    "Night time...resting until next KST 9am: {} seconds",
+                    difference
+                ),
+            }
+
+            match scheduler.sleep_until(wake_at).await {
+                Wake::Elapsed => continue 'main,
+                Wake::Triggered => println!("Manual sync requested"),
+                Wake::Shutdown => break 'main,
+            }
+        }
+
+        println!("Parsing notices now...");
+
+        // Not raced against shutdown, so insert_many is never cut off halfway
+        let result = scheduler.sync(notice_collection).await;
+
+        if scheduler.is_shutdown() {
+            break 'main;
+        }
+
+        let rest = match result {
+            Ok(notices) => {
+                if let Ok(path) = std::env::var("ICS_FILE") {
+                    let query = NoticeQuery::default();
+                    if let Err(e) = ics::write_ics(notice_collection, &query, path.as_ref()).await {
+                        println!("Failed to write {path}: {e}");
+                    }
+                }
+                let today = Utc::now().with_timezone(&Seoul).date_naive();
+                match calendar::break_on(calendar_collection, today).await {
+                    Ok(Some(event)) => {
+                        println!(
+                            "Updated {} notices!, {} so resting 3 hours...",
+                            notices.len(),
+                            event.title
+                        );
+                        chrono::Duration::hours(3)
+                    }
+                    _ => {
+                        println!("Updated {} notices!, resting 30 mins...", notices.len());
+                        chrono::Duration::seconds(1800)
+                    }
+                }
+            }
+            Err(e) => {
+                // eprintln!("Error: {}", e);
+                println!("Encountered an {e}, resting for 5 mins...");
+                chrono::Duration::seconds(300)
+            }
+        };
+
+        if scheduler
+            .sleep_until(Utc::now().with_timezone(&Seoul) + rest)
+            .await
+            == Wake::Shutdown
+        {
+            break 'main;
+        }
+    }
+
+    // The digests may be syncing too, which mustn't be cut off by the runtime going down
+    if let Some(digests) = digests {
+        if let Err(e) = digests.await {
+            println!("Digests stopped: {e}");
+        }
+    }
+    println!("Bye!");
+}
+
+/// Sends the scheduled digests every morning at [`next_digest_time`] until shutdown.
+///
+/// Notices are synced first, the loop not running in the evening nor on weekends. While the
+/// updater is paused, the digests only cover what's already stored.
+async fn run_digests(
+    notice_collection: Collection<Notice>,
+    scheduler: Arc<Scheduler>,
+    mail: MailConfig,
+    periods: Vec<DigestPeriod>,
+) {
+    loop {
+        let wake_at = next_digest_time(Utc::now().with_timezone(&Seoul));
+        if scheduler.wait_until(wake_at).await == Wake::Shutdown {
+            break;
+        }
+
+        if scheduler.is_paused() {
+            println!("Paused, sending the digests without syncing");
+        } else {
+            match scheduler.sync(&notice_collection).await {
+                Ok(notices) => println!("Synced {} notices for the digests", notices.len()),
+                // The digests still cover what was synced before
+                Err(e) => println!("Failed to sync notices for the digests: {e}"),
+            }
+        }
+        if scheduler.is_shutdown() {
+            break;
+        }
+
+        for &period in &periods {
+            if period == DigestPeriod::Weekly && wake_at.weekday() != Weekday::Mon {
+                continue;
+            }
+
+            let (from, to) = period.last_window(wake_at);
+            let result = match digest::collect_digest(&notice_collection, period, from, to).await {
+                Ok(digest) if digest.is_empty() => {
+                    println!("No notices for the {period:?} digest, skipping");
+                    continue;
+                }
+                Ok(digest) => mail.send(&digest).await.map(|_| digest.len()),
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(count) => println!("Sent the {period:?} digest of {count} notices"),
+                Err(e) => println!("Failed to send the {period:?} digest: {e}"),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_next_active_time() {
+    // Wednesday 14:00 KST
+    let now = Seoul.with_ymd_and_hms(2023, 4, 12, 14, 0, 0).unwrap();
+    assert_eq!(next_active_time(now), None);
+
+    // Wednesday 20:00 KST -> Thursday 9:30
+    let now = Seoul.with_ymd_and_hms(2023, 4, 12, 20, 0, 0).unwrap();
+    let expected = Seoul.with_ymd_and_hms(2023, 4, 13, 9, 30, 0).unwrap();
+    assert_eq!(next_active_time(now), Some(expected));
+
+    // Saturday -> Monday 9:00
+    let now = Seoul.with_ymd_and_hms(2023, 4, 15, 11, 0, 0).unwrap();
+    let expected = Seoul.with_ymd_and_hms(2023, 4, 17, 9, 0, 0).unwrap();
+    assert_eq!(next_active_time(now), Some(expected));
+}
+
+#[test]
+fn test_next_digest_time() {
+    let now = Seoul.with_ymd_and_hms(2023, 4, 12, 7, 59, 0).unwrap();
+    let expected = Seoul.with_ymd_and_hms(2023, 4, 12, 8, 0, 0).unwrap();
+    assert_eq!(next_digest_time(now), expected);
+
+    let now = Seoul.with_ymd_and_hms(2023, 4, 12, 8, 0, 0).unwrap();
+    let expected = Seoul.with_ymd_and_hms(2023, 4, 13, 8, 0, 0).unwrap();
+    assert_eq!(next_digest_time(now), expected);
+}
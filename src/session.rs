@@ -0,0 +1,277 @@
+use crate::browser;
+use crate::course::{self, CategoryCourses, CourseCategory, CourseResp, Semester};
+use crate::http::HttpClient;
+use crate::MY_USER_AGENT;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{anyhow, Context, Result};
+use argon2::Argon2;
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, COOKIE, USER_AGENT};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Where the session is kept unless `HAKSA_SESSION_FILE` says otherwise.
+pub const DEFAULT_SESSION_FILE: &str = "./user_data/haksa_session";
+
+/// Part of the `JSESSIONID` of a session logged in to the HAKSA servlet.
+const HAKSA_SERVLET: &str = "chusa_servlet_HAKSA01";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Stored {
+    jsession: String,
+    saved_at: DateTime<Utc>,
+}
+
+/// A mhaksa login (the `JSESSIONID` bound to the HAKSA servlet), kept on disk encrypted between
+/// runs so the browser only logs in through SSO once the stored one has expired.
+pub struct HaksaSession {
+    path: PathBuf,
+    /// `HAKSA_SESSION_KEY`, without which the session lives as long as the process
+    secret: Option<String>,
+    jsession: String,
+}
+
+impl HaksaSession {
+    /// The stored session if the server still takes it, a new browser login otherwise.
+    ///
+    /// `HAKSA_SESSION_FILE` sets where it's stored and `HAKSA_SESSION_KEY` the secret it's
+    /// encrypted with, nothing being stored without one.
+    pub async fn open() -> Result<Self> {
+        let secret = std::env::var("HAKSA_SESSION_KEY")
+            .ok()
+            .filter(|secret| !secret.is_empty());
+        if secret.is_none() {
+            eprintln!("No HAKSA_SESSION_KEY, the session won't be stored");
+        }
+
+        let mut session = HaksaSession {
+            path: session_file(),
+            secret,
+            jsession: String::new(),
+        };
+
+        match session.load() {
+            Ok(Some(stored)) => match check(&stored.jsession).await {
+                Ok(true) => {
+                    eprintln!("Reusing the session saved at {}", stored.saved_at);
+                    session.jsession = stored.jsession;
+                    return Ok(session);
+                }
+                Ok(false) => eprintln!("Stored session expired"),
+                Err(e) => eprintln!("Couldn't check the stored session, logging in again: {e:#}"),
+            },
+            Ok(None) => {}
+            // A changed key or a damaged file, logging in again replaces it
+            Err(e) => eprintln!("Ignoring the stored session: {e:#}"),
+        }
+
+        session.refresh().await?;
+        Ok(session)
+    }
+
+    pub fn jsession(&self) -> &str {
+        &self.jsession
+    }
+
+    /// Logs in through the browser and stores the new session.
+    pub async fn refresh(&mut self) -> Result<()> {
+        self.jsession = browser::login_and_get_jsession().await?;
+        self.save()
+    }
+
+    /// Fetches `categories` like [`course::fetch_categories`], logging in again and refetching
+    /// the failed ones once if the session expired along the way.
+    pub async fn fetch_categories(
+        &mut self,
+        semester: &Semester,
+        categories: &[CourseCategory],
+        concurrency: usize,
+    ) -> Result<Vec<CategoryCourses>> {
+        let mut fetched =
+            course::fetch_categories(semester, categories, &self.jsession, concurrency).await;
+        let failed: Vec<CourseCategory> = fetched
+            .iter()
+            .filter(|fetched| fetched.courses.is_err())
+            .map(|fetched| fetched.category)
+            .collect();
+        // A check that fails too is taken for an expired session
+        if failed.is_empty() || check(&self.jsession).await.unwrap_or(false) {
+            return Ok(fetched);
+        }
+
+        eprintln!("Session expired during the fetch, logging in again");
+        self.refresh().await?;
+        let refetched =
+            course::fetch_categories(semester, &failed, &self.jsession, concurrency).await;
+        for refetched in refetched {
+            if let Some(slot) = fetched
+                .iter_mut()
+                .find(|fetched| fetched.category == refetched.category)
+            {
+                *slot = refetched;
+            }
+        }
+        Ok(fetched)
+    }
+
+    fn load(&self) -> Result<Option<Stored>> {
+        let Some(secret) = &self.secret else {
+            return Ok(None);
+        };
+        let sealed = match std::fs::read(&self.path) {
+            Ok(sealed) => sealed,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let stored: Stored = serde_json::from_slice(&open_sealed(secret, &sealed)?)?;
+        Ok(Some(stored).filter(|stored| stored.jsession.contains(HAKSA_SERVLET)))
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(secret) = &self.secret else {
+            return Ok(());
+        };
+        let stored = Stored {
+            jsession: self.jsession.clone(),
+            saved_at: Utc::now(),
+        };
+        let sealed = seal(secret, &serde_json::to_vec(&stored)?)?;
+        write_private(&self.path, &sealed)
+            .with_context(|| format!("Storing the session at {}", self.path.display()))
+    }
+}
+
+/// `HAKSA_SESSION_FILE`, or [`DEFAULT_SESSION_FILE`].
+fn session_file() -> PathBuf {
+    std::env::var("HAKSA_SESSION_FILE")
+        .ok()
+        .filter(|path| !path.trim().is_empty())
+        .map_or_else(|| PathBuf::from(DEFAULT_SESSION_FILE), PathBuf::from)
+}
+
+/// Deletes the stored session, the next [`HaksaSession::open`] logging in again.
+pub fn forget() -> Result<()> {
+    match std::fs::remove_file(session_file()) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Whether the server still takes `jsession`, asking for the credit exchange courses of an
+/// unknown department: an empty list when logged in, an error code otherwise.
+pub async fn check(jsession: &str) -> Result<bool> {
+    if !jsession.contains(HAKSA_SERVLET) {
+        return Ok(false);
+    }
+    let semester = Semester::configured()?;
+    let payload = serde_json::json!({
+        "url": "uni/uni/cour/lssn/findCourLecturePlanDocumentReg.action",
+        "param": {
+            "strYy": semester.year.to_string(),
+            "strShtmCd": semester.term.code(),
+            "strSubmattFg": CourseCategory::CreditExchange.code(),
+            "strSustcd": "-",
+            "strMjCd": "",
+            "strSubmattFldFg": "",
+            "strCoopOpenYn": "공동개설"
+        }
+    });
+
+    let course_link = std::env::var("COURSE").map_err(|_| anyhow!("COURSE must be set"))?;
+    let res = HttpClient::shared()?
+        .post_json(
+            &course_link,
+            haksa_headers(jsession)?,
+            &payload,
+            Duration::from_secs(10),
+            |_| false,
+        )
+        .await?;
+
+    // The SSO login page comes back instead of JSON once the session is gone
+    Ok(serde_json::from_str::<CourseResp>(&res.body)
+        .is_ok_and(|response| !response.var_list.session_expired()))
+}
+
+/// Headers of a JSON request to mhaksa with `jsession`.
+pub fn haksa_headers(jsession: &str) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    headers.insert(USER_AGENT, HeaderValue::from_static(MY_USER_AGENT));
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers.insert(
+        COOKIE,
+        HeaderValue::from_str(&format!("JSESSIONID={jsession};"))
+            .context("JSESSIONID isn't a valid header value")?,
+    );
+    Ok(headers)
+}
+
+/// The key derived from `secret` with Argon2.
+fn cipher(secret: &str, salt: &[u8]) -> Result<Aes256Gcm> {
+    let mut key = Key::<Aes256Gcm>::default();
+    Argon2::default()
+        .hash_password_into(secret.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Deriving the session key failed: {e}"))?;
+    Ok(Aes256Gcm::new(&key))
+}
+
+/// The salt, then the nonce, then the ciphertext.
+fn seal(secret: &str, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher(secret, &salt)?
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| anyhow!("Encrypting the session failed"))?;
+    Ok([&salt, nonce.as_slice(), &ciphertext].concat())
+}
+
+fn open_sealed(secret: &str, sealed: &[u8]) -> Result<Vec<u8>> {
+    if sealed.len() < SALT_LEN + NONCE_LEN {
+        return Err(anyhow!("Stored session is truncated"));
+    }
+    let (salt, sealed) = sealed.split_at(SALT_LEN);
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    cipher(secret, salt)?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Stored session can't be decrypted with this key"))
+}
+
+/// Written readable by the owner only.
+fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+    // The mode only applies to a new file
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    std::io::Write::write_all(&mut file, data)?;
+    Ok(())
+}
+
+#[test]
+fn test_seal() {
+    let sealed = seal("secret", b"JSESSIONID").unwrap();
+    assert!(!sealed.windows(10).any(|window| window == b"JSESSIONID"));
+    assert_eq!(open_sealed("secret", &sealed).unwrap(), b"JSESSIONID");
+    assert!(open_sealed("other", &sealed).is_err());
+    assert!(open_sealed("secret", &sealed[..SALT_LEN + 8]).is_err());
+
+    // A fresh salt each time
+    assert_ne!(
+        sealed[..SALT_LEN],
+        seal("secret", b"JSESSIONID").unwrap()[..SALT_LEN]
+    );
+}
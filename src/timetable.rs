@@ -0,0 +1,537 @@
+use crate::building::Classroom;
+use crate::course::Course;
+use anyhow::{anyhow, Result};
+use chrono::{Duration, NaiveTime, Weekday};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+/// When and where a course meets once a week.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeSlot {
+    pub weekday: Weekday,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    /// e.g. "율곡관301", `None` if not given
+    pub room: Option<String>,
+    /// Held online at that time, e.g. "월 10:30-11:45(온라인)"
+    #[serde(default)]
+    pub online: bool,
+    /// `room` resolved to a building, see [`crate::building::Campus::parse_classroom`]
+    #[serde(default)]
+    pub classroom: Option<Classroom>,
+}
+
+impl TimeSlot {
+    /// Both meet at the same time on the same day.
+    pub fn overlaps(&self, other: &TimeSlot) -> bool {
+        self.weekday == other.weekday && self.start < other.end && other.start < self.end
+    }
+}
+
+/// Start and end of period `period`, "1" to "14" (hourly, 50 minutes from 09:00) or "A" to "H"
+/// (75 minutes, every 90 minutes from 09:00).
+fn period_time(period: &str) -> Option<(NaiveTime, NaiveTime)> {
+    let nine = NaiveTime::from_hms_opt(9, 0, 0)?;
+    let (start, minutes) = match period.parse::<i64>() {
+        Ok(n @ 1..=14) => (nine + Duration::hours(n - 1), 50),
+        Ok(_) => return None,
+        Err(_) => {
+            let letter = period.chars().next()?.to_ascii_uppercase();
+            if !('A'..='H').contains(&letter) || period.len() != 1 {
+                return None;
+            }
+            let n = (letter as u8 - b'A') as i64;
+            (nine + Duration::minutes(90 * n), 75)
+        }
+    };
+    Some((start, start + Duration::minutes(minutes)))
+}
+
+fn regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        let day = r"(?:[월화수목금토일]|mon|tue|wed|thu|fri|sat|sun)(?:[a-z]*\.?|요일)?";
+        let period = r"(?:\d{1,2}|[a-h])";
+        Regex::new(&format!(
+            r"(?i)(?P<days>{day}(?:\s*[,/·]?\s*{day})*)\s*(?:(?P<start>\d{{1,2}}:\d{{2}})\s*[-~]\s*(?P<end>\d{{1,2}}:\d{{2}})|(?P<periods>{period}(?:\s*[,\-~]\s*{period})*)(?:\s*교시)?\b)(?:\s*\((?P<paren>[^)]*)\))?"
+        ))
+        .unwrap()
+    })
+}
+
+fn time_range_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(r"(?P<start>\d{1,2}:\d{2})\s*[-~]\s*(?P<end>\d{1,2}:\d{2})").unwrap()
+    })
+}
+
+fn day_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"(?i)[월화수목금토일]|mon|tue|wed|thu|fri|sat|sun").unwrap())
+}
+
+/// 온라인, 비대면, 사이버, 원격, online, cyber, remote, e-learning
+pub fn is_online(text: &str) -> bool {
+    let text = text.to_lowercase();
+    [
+        "온라인",
+        "비대면",
+        "사이버",
+        "원격",
+        "online",
+        "cyber",
+        "remote",
+        "e-learning",
+    ]
+    .iter()
+    .any(|marker| text.contains(marker))
+}
+
+/// Reads the weekly slots of a `class_time` string, in the order written.
+///
+/// Takes Korean and English days, one or more of them per time ("월,수 10:30-11:45",
+/// "Mon/Wed 10:30-11:45"), period numbers or letters ("화 3-4", "목 B"), a time or a room in
+/// parentheses after a slot, and online markers. Strings only saying it's to be announced or
+/// held online without a time give no slots.
+pub fn parse_class_time(text: &str) -> Vec<TimeSlot> {
+    let mut slots: Vec<TimeSlot> = vec![];
+
+    for caps in regex().captures_iter(text) {
+        let paren = caps.name("paren").map(|p| p.as_str().trim());
+        let paren_time = paren.and_then(|p| time_range_regex().captures(p));
+
+        let times: Vec<(NaiveTime, NaiveTime)> =
+            if let (Some(start), Some(end)) = (caps.name("start"), caps.name("end")) {
+                parse_range(start.as_str(), end.as_str())
+                    .into_iter()
+                    .collect()
+            } else if let Some(paren_time) = &paren_time {
+                parse_range(&paren_time["start"], &paren_time["end"])
+                    .into_iter()
+                    .collect()
+            } else {
+                parse_periods(&caps["periods"])
+            };
+
+        let online = paren.is_some_and(is_online);
+        let room = paren
+            .filter(|p| paren_time.is_none() && !p.is_empty() && !online)
+            .map(String::from);
+
+        for day in day_regex().find_iter(&caps["days"]) {
+            let Some(weekday) = parse_weekday(day.as_str()) else {
+                continue;
+            };
+            for &(start, end) in &times {
+                let slot = TimeSlot {
+                    weekday,
+                    start,
+                    end,
+                    room: room.clone(),
+                    online,
+                    classroom: None,
+                };
+                if !slots.contains(&slot) {
+                    slots.push(slot);
+                }
+            }
+        }
+    }
+
+    slots
+}
+
+fn parse_range(start: &str, end: &str) -> Option<(NaiveTime, NaiveTime)> {
+    let start = NaiveTime::parse_from_str(start, "%H:%M").ok()?;
+    let end = NaiveTime::parse_from_str(end, "%H:%M").ok()?;
+    (start < end).then_some((start, end))
+}
+
+/// "1,2,4" -> 09:00-10:50 and 12:00-12:50; consecutive periods make one slot.
+fn parse_periods(periods: &str) -> Vec<(NaiveTime, NaiveTime)> {
+    let mut numbers: Vec<String> = vec![];
+    for part in periods.split(',') {
+        match part.split_once(['-', '~']) {
+            Some((from, to)) => {
+                let (from, to) = (from.trim(), to.trim());
+                match (from.parse::<u32>(), to.parse::<u32>()) {
+                    (Ok(from), Ok(to)) => numbers.extend((from..=to).map(|n| n.to_string())),
+                    _ => {
+                        let (Some(from), Some(to)) = (from.chars().next(), to.chars().next())
+                        else {
+                            continue;
+                        };
+                        numbers.extend(
+                            (from.to_ascii_uppercase()..=to.to_ascii_uppercase()).map(String::from),
+                        );
+                    }
+                }
+            }
+            None => numbers.push(part.trim().to_string()),
+        }
+    }
+
+    let mut times: Vec<(NaiveTime, NaiveTime)> =
+        numbers.iter().filter_map(|n| period_time(n)).collect();
+    times.sort();
+
+    let mut merged: Vec<(NaiveTime, NaiveTime)> = vec![];
+    for (start, end) in times {
+        match merged.last_mut() {
+            // Periods are 10 or 15 minutes apart
+            Some(last) if start <= last.1 + Duration::minutes(15) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn parse_weekday(day: &str) -> Option<Weekday> {
+    Some(match day.to_lowercase().as_str() {
+        "월" | "mon" => Weekday::Mon,
+        "화" | "tue" => Weekday::Tue,
+        "수" | "wed" => Weekday::Wed,
+        "목" | "thu" => Weekday::Thu,
+        "금" | "fri" => Weekday::Fri,
+        "토" | "sat" => Weekday::Sat,
+        "일" | "sun" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+/// A class section, e.g. ("F001", "1").
+pub type Pick = (String, String);
+
+fn pick(course: &Course) -> Pick {
+    (course.subject_code.clone(), course.class_number.clone())
+}
+
+/// Two picked sections meeting at the same time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Conflict {
+    pub first: Pick,
+    pub second: Pick,
+    pub weekday: Weekday,
+    /// When both meet
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+/// The sections of `courses` matching `picks`, in the order of `picks`.
+pub fn find_picks<'a>(courses: &'a [Course], picks: &[Pick]) -> Result<Vec<&'a Course>> {
+    picks
+        .iter()
+        .map(|(code, class)| {
+            courses
+                .iter()
+                .find(|course| &course.subject_code == code && &course.class_number == class)
+                .ok_or_else(|| anyhow!("No section {class} of {code}"))
+        })
+        .collect()
+}
+
+/// Every overlap between the slots of two different sections of `courses`.
+pub fn find_conflicts(courses: &[&Course]) -> Vec<Conflict> {
+    let mut conflicts = vec![];
+    for (i, first) in courses.iter().enumerate() {
+        for second in &courses[i + 1..] {
+            for a in &first.time_slots {
+                for b in second.time_slots.iter().filter(|b| a.overlaps(b)) {
+                    conflicts.push(Conflict {
+                        first: pick(first),
+                        second: pick(second),
+                        weekday: a.weekday,
+                        start: a.start.max(b.start),
+                        end: a.end.min(b.end),
+                    });
+                }
+            }
+        }
+    }
+    conflicts
+}
+
+/// What a generated timetable must (hard) or should (preferred) look like.
+#[derive(Debug, Clone, Default)]
+pub struct Constraints {
+    /// No class starting before, e.g. 10:00 for no 9am classes
+    pub earliest_start: Option<NaiveTime>,
+    /// No class ending after
+    pub latest_end: Option<NaiveTime>,
+    /// Days without classes, e.g. Friday
+    pub free_days: Vec<Weekday>,
+    /// Upper bound on the sum of `credit_points`
+    pub max_credits: Option<f32>,
+    /// Sections taught by them rank higher
+    pub preferred_lecturers: Vec<String>,
+}
+
+impl Constraints {
+    fn allows(&self, course: &Course) -> bool {
+        course.time_slots.iter().all(|slot| {
+            self.earliest_start.is_none_or(|start| slot.start >= start)
+                && self.latest_end.is_none_or(|end| slot.end <= end)
+                && !self.free_days.contains(&slot.weekday)
+        })
+    }
+
+    fn prefers(&self, course: &Course) -> bool {
+        self.preferred_lecturers
+            .iter()
+            .any(|lecturer| course.main_lecturer_name.contains(lecturer.as_str()))
+    }
+}
+
+/// A conflict-free set of sections, one per taken subject of the wishlist.
+#[derive(Debug, Clone, Serialize)]
+pub struct Timetable<'a> {
+    pub courses: Vec<&'a Course>,
+    pub credits: f32,
+    /// Subjects of the wishlist left out
+    pub missing: Vec<String>,
+    /// Sections taught by a preferred lecturer
+    pub preferred: usize,
+    /// Days with classes
+    pub days: usize,
+    /// Minutes between classes on the same day
+    pub gap_minutes: i64,
+}
+
+impl<'a> Timetable<'a> {
+    fn new(courses: Vec<&'a Course>, wishlist: &[String], constraints: &Constraints) -> Self {
+        let mut slots: Vec<&TimeSlot> = courses.iter().flat_map(|c| &c.time_slots).collect();
+        slots.sort_by_key(|slot| (slot.weekday.num_days_from_monday(), slot.start));
+
+        let days: HashSet<Weekday> = slots.iter().map(|slot| slot.weekday).collect();
+        let gap_minutes = slots
+            .windows(2)
+            .filter(|pair| pair[0].weekday == pair[1].weekday)
+            .map(|pair| (pair[1].start - pair[0].end).num_minutes().max(0))
+            .sum();
+
+        Timetable {
+            credits: courses.iter().map(|course| course.credit_points).sum(),
+            missing: wishlist
+                .iter()
+                .filter(|code| !courses.iter().any(|course| &&course.subject_code == code))
+                .cloned()
+                .collect(),
+            preferred: courses.iter().filter(|c| constraints.prefers(c)).count(),
+            days: days.len(),
+            gap_minutes,
+            courses,
+        }
+    }
+
+    /// Best first: most wished subjects, then preferred lecturers, fewer days, shorter gaps.
+    fn rank(&self) -> (usize, Reverse<usize>, usize, i64) {
+        (
+            self.missing.len(),
+            Reverse(self.preferred),
+            self.days,
+            self.gap_minutes,
+        )
+    }
+}
+
+/// Partial timetables tried before giving up on finding better ones.
+const MAX_SEARCHED: usize = 100_000;
+
+/// The best `limit` conflict-free timetables taking sections of the `wishlist` subjects
+/// (by `subject_code`) among `courses`.
+///
+/// Subjects are taken in wishlist order, so earlier ones win when not all of them fit.
+/// Sections breaking a hard constraint are never picked.
+pub fn build_timetables<'a>(
+    courses: &'a [Course],
+    wishlist: &[String],
+    constraints: &Constraints,
+    limit: usize,
+) -> Vec<Timetable<'a>> {
+    // Sections of each wished subject, once even if listed under several categories
+    let mut seen = HashSet::new();
+    let sections: Vec<Vec<&Course>> = wishlist
+        .iter()
+        .map(|code| {
+            courses
+                .iter()
+                .filter(|course| &course.subject_code == code && constraints.allows(course))
+                .filter(|course| seen.insert(pick(course)))
+                .collect()
+        })
+        .collect();
+
+    let mut search = Search {
+        sections: &sections,
+        constraints,
+        picked: vec![],
+        found: vec![],
+        searched: 0,
+    };
+    search.run(0, 0.0);
+
+    let mut timetables: Vec<Timetable> = search
+        .found
+        .into_iter()
+        .map(|courses| Timetable::new(courses, wishlist, constraints))
+        .collect();
+    timetables.sort_by_key(Timetable::rank);
+    timetables.truncate(limit);
+    timetables
+}
+
+struct Search<'a, 's> {
+    sections: &'s [Vec<&'a Course>],
+    constraints: &'s Constraints,
+    picked: Vec<&'a Course>,
+    found: Vec<Vec<&'a Course>>,
+    searched: usize,
+}
+
+impl<'a, 's> Search<'a, 's> {
+    /// Picks a section of subject `i` or leaves it out, then goes on with the next one.
+    fn run(&mut self, i: usize, credits: f32) {
+        self.searched += 1;
+        if self.searched > MAX_SEARCHED {
+            return;
+        }
+        let Some(sections) = self.sections.get(i) else {
+            if !self.picked.is_empty() {
+                self.found.push(self.picked.clone());
+            }
+            return;
+        };
+
+        let mut fitted = false;
+        for &section in sections {
+            let credits = credits + section.credit_points;
+            if self
+                .constraints
+                .max_credits
+                .is_some_and(|max| credits > max)
+                || !find_conflicts(&[&self.picked[..], &[section]].concat()).is_empty()
+            {
+                continue;
+            }
+            fitted = true;
+            self.picked.push(section);
+            self.run(i + 1, credits);
+            self.picked.pop();
+        }
+
+        // Leaving a subject out is only worth it if none of its sections fit
+        if !fitted {
+            self.run(i + 1, credits);
+        }
+    }
+}
+
+#[test]
+fn test_parse_class_time() {
+    let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+
+    let slots = parse_class_time("월 10:30-11:45(율곡관301) 수 10:30-11:45(율곡관301)");
+    assert_eq!(slots.len(), 2);
+    assert_eq!(
+        slots[0],
+        TimeSlot {
+            weekday: Weekday::Mon,
+            start: time(10, 30),
+            end: time(11, 45),
+            room: Some("율곡관301".to_string()),
+            online: false,
+            classroom: None,
+        }
+    );
+    assert_eq!(slots[1].weekday, Weekday::Wed);
+
+    let slots = parse_class_time("Tue,Thu 13:30~14:45");
+    assert_eq!(slots.len(), 2);
+    assert_eq!(slots[1].weekday, Weekday::Thu);
+    assert_eq!(slots[1].room, None);
+
+    // Periods: consecutive ones make a slot, a time in parentheses wins
+    let slots = parse_class_time("화 1,2,4 금 B(10:30-11:45)");
+    assert_eq!(slots.len(), 3);
+    assert_eq!((slots[0].start, slots[0].end), (time(9, 0), time(10, 50)));
+    assert_eq!((slots[1].start, slots[1].end), (time(12, 0), time(12, 50)));
+    assert_eq!(slots[2].weekday, Weekday::Fri);
+    assert_eq!((slots[2].start, slots[2].end), (time(10, 30), time(11, 45)));
+
+    let slots = parse_class_time("목 3-4교시 (온라인)");
+    assert_eq!((slots[0].start, slots[0].end), (time(11, 0), time(12, 50)));
+    assert!(slots[0].online);
+    assert_eq!(slots[0].room, None);
+
+    assert!(parse_class_time("미정").is_empty());
+    assert!(parse_class_time("TBA").is_empty());
+    assert!(parse_class_time("온라인(비대면) 강의").is_empty());
+    assert!(is_online("Online (Cyber)"));
+}
+
+#[test]
+fn test_build_timetables() {
+    let course = |code: &str, class: &str, lecturer: &str, time: &str| {
+        let mut course = Course {
+            subject_code: code.to_string(),
+            class_number: class.to_string(),
+            main_lecturer_name: lecturer.to_string(),
+            class_time_korean: time.to_string(),
+            credit_points: 3.0,
+            ..Default::default()
+        };
+        course.time_slots = parse_class_time(time);
+        course
+    };
+    let courses = vec![
+        course("A", "1", "김", "월 09:00-10:15 수 09:00-10:15"),
+        course("A", "2", "이", "화 10:30-11:45 목 10:30-11:45"),
+        course("B", "1", "박", "월 09:30-10:45"),
+        course("B", "2", "최", "금 13:30-16:15"),
+        course("C", "1", "정", "화 10:30-11:45"),
+        course("D", "1", "강", "온라인"),
+    ];
+
+    let picks = [
+        ("A".to_string(), "1".to_string()),
+        ("B".to_string(), "1".to_string()),
+        ("D".to_string(), "1".to_string()),
+    ];
+    let conflicts = find_conflicts(&find_picks(&courses, &picks).unwrap());
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].weekday, Weekday::Mon);
+    assert_eq!(
+        (conflicts[0].start, conflicts[0].end),
+        (
+            NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+            NaiveTime::from_hms_opt(10, 15, 0).unwrap()
+        )
+    );
+    assert!(find_picks(&courses, &[("A".to_string(), "9".to_string())]).is_err());
+
+    let wishlist: Vec<String> = ["A", "B", "C", "D"].map(String::from).to_vec();
+    let timetables = build_timetables(&courses, &wishlist, &Constraints::default(), 10);
+    // A-1 with B-2 and C-1, or A-2 (clashing with C-1) with B-1 or B-2
+    let best = &timetables[0];
+    assert!(best.missing.is_empty());
+    assert_eq!(best.credits, 12.0);
+    assert!(find_conflicts(&best.courses).is_empty());
+
+    let constraints = Constraints {
+        earliest_start: NaiveTime::from_hms_opt(10, 0, 0),
+        free_days: vec![Weekday::Fri],
+        max_credits: Some(9.0),
+        preferred_lecturers: vec!["이".to_string()],
+        ..Default::default()
+    };
+    let timetables = build_timetables(&courses, &wishlist, &constraints, 10);
+    let best = &timetables[0];
+    assert_eq!(best.courses[0].class_number, "2");
+    assert_eq!(best.missing, vec!["B".to_string(), "C".to_string()]);
+    assert_eq!(best.preferred, 1);
+    assert!(best.credits <= 9.0);
+}
@@ -0,0 +1,183 @@
+use anyhow::{anyhow, Context, Result};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, RootCertStore, ServerName};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tracing::warn;
+
+/// Certificate checks of the shared scraping client.
+///
+/// System roots are trusted by default, `ca_bundle` adds Ajou's intermediate chain on top.
+#[derive(Debug, Clone, Default)]
+pub struct TlsConfig {
+    /// Extra PEM bundle trusted on top of the system roots
+    pub ca_bundle: Option<PathBuf>,
+    /// SHA-256 fingerprints (hex) of which at least one must appear in the server's chain
+    pub pins: Vec<String>,
+    /// Skips every certificate check, only for debugging
+    pub insecure: bool,
+}
+
+impl TlsConfig {
+    /// Reads `TLS_CA_BUNDLE`, `TLS_PINS` (comma separated) and `TLS_INSECURE`.
+    pub fn from_env() -> Self {
+        TlsConfig {
+            ca_bundle: std::env::var("TLS_CA_BUNDLE").ok().map(PathBuf::from),
+            pins: std::env::var("TLS_PINS")
+                .map(|pins| {
+                    pins.split(',')
+                        .map(str::trim)
+                        .filter(|pin| !pin.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            insecure: std::env::var("TLS_INSECURE")
+                .is_ok_and(|v| matches!(v.trim(), "1" | "true" | "yes")),
+        }
+    }
+
+    /// Builds the rustls config for `reqwest::ClientBuilder::use_preconfigured_tls`.
+    pub fn client_config(&self) -> Result<ClientConfig> {
+        let verifier: Arc<dyn ServerCertVerifier> = if self.insecure {
+            warn!("TLS certificate verification is DISABLED (TLS_INSECURE)");
+            println!("!!! WARNING: TLS certificate verification is DISABLED (TLS_INSECURE) !!!");
+            println!("!!! Session cookies can be read by anyone on the network path      !!!");
+            Arc::new(NoVerification)
+        } else {
+            let verifier = WebPkiVerifier::new(self.root_store()?, None);
+            if self.pins.is_empty() {
+                Arc::new(verifier)
+            } else {
+                Arc::new(PinnedVerifier {
+                    inner: verifier,
+                    pins: self
+                        .pins
+                        .iter()
+                        .map(|pin| normalize_fingerprint(pin))
+                        .collect(),
+                })
+            }
+        };
+
+        Ok(ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(verifier)
+            .with_no_client_auth())
+    }
+
+    fn root_store(&self) -> Result<RootCertStore> {
+        let mut roots = RootCertStore::empty();
+
+        match rustls_native_certs::load_native_certs() {
+            Ok(certs) => {
+                let ders: Vec<Vec<u8>> = certs.into_iter().map(|cert| cert.0).collect();
+                roots.add_parsable_certificates(&ders);
+            }
+            Err(e) => warn!("Failed to load system root certificates: {e}"),
+        }
+
+        if let Some(path) = &self.ca_bundle {
+            let mut reader = BufReader::new(
+                File::open(path).with_context(|| format!("Opening CA bundle {path:?}"))?,
+            );
+            let ders = rustls_pemfile::certs(&mut reader)
+                .with_context(|| format!("Reading CA bundle {path:?}"))?;
+            if ders.is_empty() {
+                return Err(anyhow!("No certificate found in CA bundle {path:?}"));
+            }
+            for der in ders {
+                roots.add(&Certificate(der))?;
+            }
+        }
+
+        if roots.is_empty() {
+            return Err(anyhow!(
+                "No trusted root certificate, set TLS_CA_BUNDLE or install system roots"
+            ));
+        }
+        Ok(roots)
+    }
+}
+
+/// Lowercase hex without separators, so "AB:CD:..." and "abcd..." both work.
+fn normalize_fingerprint(pin: &str) -> String {
+    pin.chars()
+        .filter(char::is_ascii_hexdigit)
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+pub fn fingerprint(cert: &Certificate) -> String {
+    format!("{:x}", Sha256::digest(&cert.0))
+}
+
+/// Regular WebPKI verification plus a SHA-256 fingerprint check on the chain.
+struct PinnedVerifier {
+    inner: WebPkiVerifier,
+    pins: Vec<String>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+
+        let pinned = std::iter::once(end_entity)
+            .chain(intermediates)
+            .any(|cert| self.pins.contains(&fingerprint(cert)));
+        if !pinned {
+            warn!("Certificate pin mismatch for {server_name:?}");
+            return Err(rustls::Error::General(format!(
+                "No pinned certificate in the chain of {server_name:?}"
+            )));
+        }
+
+        Ok(verified)
+    }
+}
+
+struct NoVerification;
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        warn!("Accepting unverified certificate for {server_name:?} (TLS_INSECURE)");
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+#[test]
+fn test_normalize_fingerprint() {
+    assert_eq!(normalize_fingerprint("AB:cd:01"), "abcd01");
+    assert_eq!(normalize_fingerprint(" abcd01 "), "abcd01");
+    assert_eq!(
+        fingerprint(&Certificate(b"ajou".to_vec())),
+        "79bab90250063e553fc33bec015c6c4e15a4e9b6f452c0ea63598b42ccf1be67"
+    );
+}